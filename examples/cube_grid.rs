@@ -3,6 +3,7 @@ use serde::{Deserialize, Serialize};
 use wfc3d::cube_grid::CubeGrid;
 use wfc3d::hashset_state::HashsetState;
 use wfc3d::set_rule::*;
+use wfc3d::Solver;
 
 const RIGHT: (isize, isize, isize) = (1, 0, 0);
 const FRONT: (isize, isize, isize) = (0, 0, -1);
@@ -546,20 +547,33 @@ fn main() {
 
     let cube_dim = 100;
 
-    // TODO: set predetermined states by modifying init_fn based on current coords
     let init_fn = |_x, _y, _z| all_state.clone();
     let mut space = CubeGrid::new(cube_dim, cube_dim, 5, init_fn);
-    wfc3d::collapse(&mut space, &rule.build());
+    let rule = rule.build();
+    let result = {
+        let mut solver = Solver::new(&mut space, &rule).unwrap();
+
+        // Predetermined states are pinned before the run, so that their
+        // constraints spread through the space immediately
+        solver
+            .pin((0, 4, 0), &HashsetState::new_final(&"p-1".to_string()))
+            .expect("pinned states conflict");
+        solver.run()
+    };
+    if let Err(contradiction) = result {
+        println!("Collapse failed: {}", contradiction);
+        return;
+    }
 
     // Print out the collapsed 3x3 cube layer by layer
     for y in 0..5 {
-        print!("Layer: {}\n", y);
+        println!("Layer: {}", y);
         for z in 0..cube_dim {
             for x in 0..cube_dim {
                 print!("{:?} ", (&space[(x, y, z)]));
             }
-            print!("\n");
+            println!();
         }
-        print!("\n");
+        println!();
     }
 }
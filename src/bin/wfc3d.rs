@@ -0,0 +1,405 @@
+//! Command line tool which generates a 3D tile map from a tileset file.
+//!
+//! The tileset uses the prototype JSON format of the `cube_grid` example: an
+//! object mapping each tile name to its `weight` and `valid_neighbors`, listed
+//! in the order +x, -z, -x, +z, +y, -y. Other fields are ignored, apart from
+//! an optional `color` used for image and .vox output.
+
+use bevy_utils::HashMap;
+use clap::{Parser, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::hashset_state::HashsetState;
+use wfc3d::set_rule::*;
+use wfc3d::{Cause, Contradiction, Final, Provenance, Solver};
+
+// Neighbor directions in the order of `valid_neighbors`
+const DIRECTIONS: [(isize, isize, isize); 6] = [
+    (1, 0, 0),
+    (0, 0, -1),
+    (-1, 0, 0),
+    (0, 0, 1),
+    (0, 1, 0),
+    (0, -1, 0),
+];
+
+type Tiles = HashsetState<String>;
+type Rule = SetCollapseRule<Tiles, CubeGrid<Tiles>, WeightedSetCollapseObserver<String>>;
+
+#[derive(Deserialize)]
+struct Prototype {
+    #[serde(default = "default_weight")]
+    weight: u32,
+    valid_neighbors: [Vec<String>; 6],
+    color: Option<[u8; 3]>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// A JSON object holding the tile names, indexed as [y][z][x]
+    Json,
+    /// One PNG image per layer, with a color for each tile
+    Png,
+    /// A MagicaVoxel file with a voxel for each tile
+    Vox,
+}
+
+/// Generate a 3D tile map from a tileset with wave function collapse
+#[derive(Parser)]
+#[command(name = "wfc3d", version)]
+struct Args {
+    /// Tileset file, in the prototype JSON format
+    tileset: PathBuf,
+    /// Size of the output along the x axis
+    #[arg(long, default_value_t = 16)]
+    width: isize,
+    /// Size of the output along the z axis
+    #[arg(long, default_value_t = 16)]
+    length: isize,
+    /// Size of the output along the y axis
+    #[arg(long, default_value_t = 4)]
+    height: isize,
+    /// Random seed. A random one is picked and printed if not given.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// How many times to start over after a contradiction
+    #[arg(long, default_value_t = 10)]
+    retries: usize,
+    /// Axes along which the output wraps around, e.g. "xz"
+    #[arg(long, default_value = "")]
+    wrap: String,
+    /// Tile assumed to surround the output. Cells on the faces of the output
+    /// have to be allowed next to it. Not used on wrapping axes.
+    #[arg(long)]
+    border: Option<String>,
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+    /// Output file. PNG layers are written next to it, numbered by height.
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Size in pixels of each cell in PNG output
+    #[arg(long, default_value_t = 8)]
+    scale: u32,
+    /// Tiles left out of .vox output, such as air
+    #[arg(long)]
+    empty: Vec<String>,
+    /// Trace each contradiction back to the observations which caused it
+    #[arg(long)]
+    explain: bool,
+}
+
+fn main() {
+    if let Err(error) = run(Args::parse()) {
+        eprintln!("error: {}", error);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let file = File::open(&args.tileset)
+        .map_err(|e| format!("can't open {}: {}", args.tileset.display(), e))?;
+    let prototypes: HashMap<String, Prototype> = serde_json::from_reader(file)
+        .map_err(|e| format!("can't read {}: {}", args.tileset.display(), e))?;
+    let rule = build_rule(&prototypes)?;
+    let mut names: Vec<&String> = prototypes.keys().collect();
+    names.sort();
+
+    if args.width < 1 || args.length < 1 || args.height < 1 {
+        return Err("dimensions must be at least 1".into());
+    }
+    let wrap = (
+        args.wrap.contains('x'),
+        args.wrap.contains('y'),
+        args.wrap.contains('z'),
+    );
+    if let Some(border) = &args.border {
+        if !prototypes.contains_key(border) {
+            return Err(format!("border tile {:?} isn't in the tileset", border).into());
+        }
+    }
+    // The border is a layer of fixed cells around the output
+    let padding = match args.border {
+        Some(_) => (!wrap.0 as isize, !wrap.1 as isize, !wrap.2 as isize),
+        None => (0, 0, 0),
+    };
+    let all_tiles = Tiles::new(&names.iter().map(|n| (*n).clone()).collect::<Vec<_>>());
+    let new_grid = || {
+        let size = (
+            args.width + 2 * padding.0,
+            args.height + 2 * padding.1,
+            args.length + 2 * padding.2,
+        );
+        CubeGrid::new(size.0, size.2, size.1, |x, y, z| {
+            let inside = (padding.0..size.0 - padding.0).contains(&x)
+                && (padding.1..size.1 - padding.1).contains(&y)
+                && (padding.2..size.2 - padding.2).contains(&z);
+            match &args.border {
+                Some(border) if !inside => Tiles::new_final(border),
+                _ => all_tiles.clone(),
+            }
+        })
+        .wrapping(wrap.0, wrap.1, wrap.2)
+    };
+    let unpad = |(x, y, z): (isize, isize, isize)| (x - padding.0, y - padding.1, z - padding.2);
+
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    println!("seed: {}", seed);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let start = Instant::now();
+    let mut collapsed = None;
+    for attempt in 1..=args.retries + 1 {
+        let mut space = new_grid();
+        let (result, observations, trace) = {
+            let mut solver = Solver::with_rng(&mut space, &rule, &mut rng)?;
+            if args.explain {
+                solver.record_provenance();
+            }
+            let result = solver.run();
+            let trace = match &result {
+                Err(contradiction) if args.explain => solver.explain(contradiction.coordinate),
+                _ => Vec::new(),
+            };
+            (result, solver.observations(), trace)
+        };
+        match result {
+            Ok(()) => {
+                println!(
+                    "attempt {}: done after {} observations",
+                    attempt, observations
+                );
+                collapsed = Some(space);
+                break;
+            }
+            Err(Contradiction { coordinate }) => {
+                println!(
+                    "attempt {}: contradiction at {:?} after {} observations",
+                    attempt,
+                    unpad(coordinate),
+                    observations
+                );
+                print_trace(&trace, &rule, unpad);
+            }
+        }
+    }
+    println!("time: {:.2?}", start.elapsed());
+    let Some(space) = collapsed else {
+        return Err(format!("no solution found in {} attempts", args.retries + 1).into());
+    };
+    if let Err(violations) = wfc3d::verify(&space, &rule) {
+        for violation in violations {
+            println!("violation: {}", violation);
+        }
+    }
+
+    // The output without the border, indexed as [y][z][x]
+    let output: Vec<Vec<Vec<String>>> = (0..args.height)
+        .map(|y| {
+            (0..args.length)
+                .map(|z| {
+                    (0..args.width)
+                        .map(|x| {
+                            let coord = (x + padding.0, y + padding.1, z + padding.2);
+                            space[coord].get().unwrap()
+                        })
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+    print_counts(&output);
+
+    let colors: HashMap<&String, [u8; 3]> = prototypes
+        .iter()
+        .map(|(name, prototype)| (name, prototype.color.unwrap_or_else(|| name_color(name))))
+        .collect();
+    match args.format {
+        Format::Json => write_json(&args.output, seed, &output)?,
+        Format::Png => write_png(&args.output, args.scale, &output, &colors)?,
+        Format::Vox => write_vox(&args.output, &output, &colors, &args.empty)?,
+    }
+    Ok(())
+}
+
+fn build_rule(prototypes: &HashMap<String, Prototype>) -> Result<Rule, Box<dyn Error>> {
+    let mut all = Tiles::new(&[]);
+    let mut weights = HashMap::new();
+    for (name, prototype) in prototypes {
+        all.hashset.insert(name.clone());
+        weights.insert(name.clone(), prototype.weight);
+    }
+    let mut builder = SetCollapseRuleBuilder::new(WeightedSetCollapseObserver { weights }, all);
+    for (name, prototype) in prototypes {
+        for (direction, neighbors) in DIRECTIONS.iter().zip(prototype.valid_neighbors.iter()) {
+            if let Some(unknown) = neighbors.iter().find(|n| !prototypes.contains_key(*n)) {
+                return Err(format!("tile {:?} refers to unknown tile {:?}", name, unknown).into());
+            }
+            builder = builder.allow(
+                &Tiles::new_final(name),
+                &[(*direction, Tiles::new(neighbors))],
+            );
+        }
+    }
+    Ok(builder.build())
+}
+
+// Prints the last steps which led to a contradiction
+fn print_trace(
+    trace: &[Provenance<(isize, isize, isize), Tiles>],
+    rule: &Rule,
+    unpad: impl Fn((isize, isize, isize)) -> (isize, isize, isize),
+) {
+    let offsets = wfc3d::CollapseRule::neighbor_offsets(rule);
+    let skipped = trace.len().saturating_sub(12);
+    if skipped > 0 {
+        println!("  ... {} earlier steps", skipped);
+    }
+    for step in &trace[skipped..] {
+        let mut tiles: Vec<&String> = step.state.hashset.iter().collect();
+        tiles.sort();
+        println!("  {:?} narrowed to {:?}", unpad(step.coordinate), tiles);
+        for cause in step.causes.iter() {
+            match cause {
+                Cause::Neighbor {
+                    neighbor,
+                    offset_index,
+                    neighbor_state,
+                    removed,
+                } => {
+                    let mut states: Vec<&String> = neighbor_state.hashset.iter().collect();
+                    states.sort();
+                    let mut removed: Vec<&String> = removed
+                        .iter()
+                        .flat_map(|state| state.hashset.iter())
+                        .collect();
+                    removed.sort();
+                    println!(
+                        "    by neighbor {:?} in direction {:?}, which could be {:?}",
+                        unpad(*neighbor),
+                        offsets[*offset_index],
+                        states
+                    );
+                    println!(
+                        "      removing {:?}, which don't allow it in that direction",
+                        removed
+                    );
+                }
+                cause => println!("    {:?}", cause),
+            }
+        }
+    }
+}
+
+fn print_counts(output: &[Vec<Vec<String>>]) {
+    let mut counts: HashMap<&String, usize> = HashMap::new();
+    for tile in output.iter().flatten().flatten() {
+        *counts.entry(tile).or_default() += 1;
+    }
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    for (tile, count) in counts {
+        println!("{:>8} {}", count, tile);
+    }
+}
+
+// A color which stays the same for a tile name between runs
+fn name_color(name: &str) -> [u8; 3] {
+    let mut hash: u32 = 0x811c_9dc5;
+    for byte in name.bytes() {
+        hash = (hash ^ byte as u32).wrapping_mul(0x0100_0193);
+    }
+    let [r, g, b, _] = hash.to_le_bytes();
+    [r, g, b]
+}
+
+#[derive(Serialize)]
+struct JsonOutput<'a> {
+    seed: u64,
+    width: usize,
+    length: usize,
+    height: usize,
+    tiles: &'a [Vec<Vec<String>>],
+}
+
+fn write_json(path: &Path, seed: u64, output: &[Vec<Vec<String>>]) -> Result<(), Box<dyn Error>> {
+    let json = JsonOutput {
+        seed,
+        width: output[0][0].len(),
+        length: output[0].len(),
+        height: output.len(),
+        tiles: output,
+    };
+    serde_json::to_writer(BufWriter::new(File::create(path)?), &json)?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
+
+fn write_png(
+    path: &Path,
+    scale: u32,
+    output: &[Vec<Vec<String>>],
+    colors: &HashMap<&String, [u8; 3]>,
+) -> Result<(), Box<dyn Error>> {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    for (y, layer) in output.iter().enumerate() {
+        let width = layer[0].len() as u32 * scale;
+        let length = layer.len() as u32 * scale;
+        let image = image::RgbImage::from_fn(width, length, |px, pz| {
+            image::Rgb(colors[&layer[(pz / scale) as usize][(px / scale) as usize]])
+        });
+        let layer_path = path.with_file_name(format!("{}_y{}.png", stem, y));
+        image.save(&layer_path)?;
+        println!("wrote {}", layer_path.display());
+    }
+    Ok(())
+}
+
+fn write_vox(
+    path: &Path,
+    output: &[Vec<Vec<String>>],
+    colors: &HashMap<&String, [u8; 3]>,
+    empty: &[String],
+) -> Result<(), Box<dyn Error>> {
+    let mut tiles: Vec<&String> = colors
+        .keys()
+        .copied()
+        .filter(|t| !empty.contains(t))
+        .collect();
+    tiles.sort();
+    if tiles.len() > 255 {
+        return Err(".vox output is limited to 255 tiles".into());
+    }
+    let mut palette = [[0; 4]; 256];
+    for (i, tile) in tiles.iter().enumerate() {
+        let [r, g, b] = colors[tile];
+        palette[i + 1] = [r, g, b, 255];
+    }
+    let (width, length, height) = (output[0][0].len(), output[0].len(), output.len());
+    let grid = CubeGrid::new(
+        width as isize,
+        length as isize,
+        height as isize,
+        |x, y, z| {
+            let tile = &output[y as usize][z as usize][x as usize];
+            tiles
+                .iter()
+                .position(|t| *t == tile)
+                .map_or(0, |i| i as u8 + 1)
+        },
+    );
+    let file = BufWriter::new(File::create(path)?);
+    wfc3d::vox::write_vox(&grid, |index| *index, Some(&palette), file)?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
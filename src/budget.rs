@@ -0,0 +1,71 @@
+use bevy_utils::{Duration, Instant};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Limits on how much work [crate::Solver::run_with_budget] may do before it
+/// is interrupted. Unset limits are unbounded.
+///
+/// * `max_observations` - The number of cells which may be observed
+/// * `max_propagation_steps` - The number of times the collapse rule may be
+///   applied to a cell while propagating
+/// * `deadline` - The time after which the run is interrupted
+/// * `cancel` - Interrupts the run once set to `true`, for example from another
+///   thread
+#[derive(Clone, Default, Debug)]
+pub struct Budget {
+    pub max_observations: Option<usize>,
+    pub max_propagation_steps: Option<usize>,
+    pub deadline: Option<Instant>,
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+impl Budget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_observations(mut self, max_observations: usize) -> Self {
+        self.max_observations = Some(max_observations);
+        self
+    }
+
+    pub fn max_propagation_steps(mut self, max_propagation_steps: usize) -> Self {
+        self.max_propagation_steps = Some(max_propagation_steps);
+        self
+    }
+
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline to `timeout` from now
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    pub fn cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+/// Outcome of a [crate::Solver::run_with_budget] which didn't hit a
+/// contradiction
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Status {
+    /// Every cell is resolved
+    Complete,
+    /// The budget ran out or the run was cancelled. The solver can be run
+    /// again to continue where it left off.
+    Interrupted,
+}
@@ -0,0 +1,270 @@
+use bevy_utils::HashMap;
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+use crate::cube_grid::{CubeBox, CubeGrid};
+use crate::{CollapseRule, Contradiction, Region, Solver, Space, State};
+
+pub type ChunkCoordinate = (isize, isize, isize);
+
+/// Generates an unbounded world as fixed-size [CubeGrid] chunks, on demand.
+///
+/// Each chunk is collapsed inside a border of padding cells, which hold the
+/// cells of already generated neighboring chunks. Their faces constrain the
+/// new chunk, so that it continues seamlessly from them. Padding cells next to
+/// chunks which haven't been generated yet are left open.
+///
+/// Chunks are seeded from the world seed and their chunk coordinate, so a
+/// chunk comes out the same every time as long as the chunks around it are
+/// generated in the same order.
+///
+/// Collapse rules are given world coordinates, so rules which depend on
+/// position carry on across chunks.
+///
+/// When a chunk hits a contradiction, a block of cells around it is reset and
+/// collapsed again, growing the block on every repair until it succeeds or
+/// runs out of repairs. Cells of other chunks are never modified.
+pub struct ChunkGenerator<'a, S: State + 'static, Rule: CollapseRule<S, CubeGrid<S>>> {
+    rule: &'a Rule,
+    open_state: S,
+    chunk_size: (isize, isize, isize),
+    seed: u64,
+    padding: isize,
+    repair_block: isize,
+    max_repairs: usize,
+    chunks: HashMap<ChunkCoordinate, CubeGrid<S>>,
+}
+
+impl<'a, S: State + 'static, Rule: CollapseRule<S, CubeGrid<S>>> ChunkGenerator<'a, S, Rule> {
+    /// * `rule` - The collapse rule to use
+    /// * `open_state` - The initial state of every cell in a new chunk
+    /// * `chunk_size` - The size of a chunk along the x, y and z axes
+    /// * `seed` - The world seed
+    pub fn new(
+        rule: &'a Rule,
+        open_state: S,
+        chunk_size: (isize, isize, isize),
+        seed: u64,
+    ) -> Self {
+        // The padding has to reach as far as the rule looks for neighbors
+        let padding = rule_reach(rule);
+        Self {
+            rule,
+            open_state,
+            chunk_size,
+            seed,
+            padding,
+            repair_block: 4,
+            max_repairs: 8,
+            chunks: HashMap::new(),
+        }
+    }
+
+    /// Sets the size of the block reset around a contradiction by the first
+    /// repair. Defaults to 4.
+    pub fn repair_block(mut self, size: isize) -> Self {
+        self.repair_block = size.max(1);
+        self
+    }
+
+    /// Sets how many repairs are attempted before a chunk fails. Defaults to 8.
+    pub fn max_repairs(mut self, repairs: usize) -> Self {
+        self.max_repairs = repairs;
+        self
+    }
+
+    /// The chunk at `chunk`, if it has been generated
+    pub fn get(&self, chunk: ChunkCoordinate) -> Option<&CubeGrid<S>> {
+        self.chunks.get(&chunk)
+    }
+
+    /// The cell at the world coordinate `coord`, if its chunk has been
+    /// generated
+    pub fn cell(&self, coord: (isize, isize, isize)) -> Option<&S> {
+        let (x, y, z) = coord;
+        let (sx, sy, sz) = self.chunk_size;
+        let chunk = (x.div_euclid(sx), y.div_euclid(sy), z.div_euclid(sz));
+        self.chunks
+            .get(&chunk)
+            .map(|grid| &grid[(x.rem_euclid(sx), y.rem_euclid(sy), z.rem_euclid(sz))])
+    }
+
+    /// Unloads the chunk at `chunk`, returning it if it had been generated.
+    /// It no longer constrains chunks generated after it.
+    pub fn remove(&mut self, chunk: ChunkCoordinate) -> Option<CubeGrid<S>> {
+        self.chunks.remove(&chunk)
+    }
+
+    /// Returns the chunk at `chunk`, generating it first if needed.
+    ///
+    /// Fails with the world coordinate of the last contradiction if the chunk
+    /// couldn't be repaired.
+    pub fn generate(
+        &mut self,
+        chunk: ChunkCoordinate,
+    ) -> Result<&CubeGrid<S>, Contradiction<(isize, isize, isize)>> {
+        if !self.chunks.contains_key(&chunk) {
+            let grid = self.collapse_chunk(chunk)?;
+            self.chunks.insert(chunk, grid);
+        }
+        Ok(&self.chunks[&chunk])
+    }
+
+    fn collapse_chunk(
+        &self,
+        chunk: ChunkCoordinate,
+    ) -> Result<CubeGrid<S>, Contradiction<(isize, isize, isize)>> {
+        let (sx, sy, sz) = self.chunk_size;
+        let p = self.padding;
+        // World coordinate of the padded grid's (0, 0, 0)
+        let origin = (chunk.0 * sx - p, chunk.1 * sy - p, chunk.2 * sz - p);
+        let interior = CubeBox {
+            min: (p, p, p),
+            max: (p + sx, p + sy, p + sz),
+        };
+
+        let mut padded = CubeGrid::new(sx + 2 * p, sz + 2 * p, sy + 2 * p, |x, y, z| {
+            if interior.contains(&(x, y, z)) {
+                return self.open_state.clone();
+            }
+            self.cell((origin.0 + x, origin.1 + y, origin.2 + z))
+                .cloned()
+                .unwrap_or_else(|| self.open_state.clone())
+        });
+
+        let mut rng = StdRng::seed_from_u64(chunk_seed(self.seed, chunk));
+        collapse_with_repairs(
+            &mut padded,
+            origin,
+            self.rule,
+            &interior,
+            &mut rng,
+            self.repair_block,
+            self.max_repairs,
+        )?;
+
+        Ok(CubeGrid::new(sx, sz, sy, |x, y, z| {
+            padded[(x + p, y + p, z + p)].clone()
+        }))
+    }
+}
+
+// The furthest distance along any axis at which the rule looks for neighbors
+pub(crate) fn rule_reach<S: State + 'static, Rule: CollapseRule<S, CubeGrid<S>>>(
+    rule: &Rule,
+) -> isize {
+    rule.neighbor_offsets()
+        .iter()
+        .map(|(dx, dy, dz)| dx.abs().max(dy.abs()).max(dz.abs()))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Collapses the cells of `grid` inside `interior`, leaving the cells around it
+/// untouched. On a contradiction, a block of `repair_block` cells around it is
+/// reset to its initial states and collapsed again, doubling the block on each
+/// of up to `max_repairs` repairs.
+///
+/// `grid` is a part of a larger space starting at `origin`. The rule is given
+/// coordinates in that space, and so is the returned contradiction.
+pub(crate) fn collapse_with_repairs<S: State + 'static, Rule: CollapseRule<S, CubeGrid<S>>>(
+    grid: &mut CubeGrid<S>,
+    origin: (isize, isize, isize),
+    rule: &Rule,
+    interior: &(impl Region<(isize, isize, isize)> + ?Sized),
+    rng: &mut impl Rng,
+    repair_block: isize,
+    max_repairs: usize,
+) -> Result<(), Contradiction<(isize, isize, isize)>> {
+    let rule = Translated { rule, origin };
+    // The solver only initializes the interior. Open cells around it are
+    // initialized here as well, so that they constrain the interior the way
+    // they will once they are collapsed themselves.
+    for coord in grid.coordinate_list().iter() {
+        if !interior.contains(coord) && grid[*coord].entropy() > 0 {
+            rule.initialize(*coord, &mut grid[*coord]);
+        }
+    }
+    let initial = grid.clone();
+    let mut block = repair_block;
+    let mut repairs = 0;
+    loop {
+        let result = Solver::with_rng(&mut *grid, &rule, &mut *rng).and_then(|mut solver| {
+            solver.confine(interior);
+            solver.run()
+        });
+        let Err(contradiction) = result else {
+            return Ok(());
+        };
+        if repairs == max_repairs {
+            return Err(Contradiction {
+                coordinate: translate(contradiction.coordinate, origin),
+            });
+        }
+        repairs += 1;
+
+        // Reset the block around the contradiction, along with any cells
+        // still left narrowed by the failed collapse
+        let (x, y, z) = contradiction.coordinate;
+        let half = block / 2;
+        let around = CubeBox {
+            min: (x - half, y - half, z - half),
+            max: (x - half + block, y - half + block, z - half + block),
+        };
+        for coord in grid.coordinate_list().iter() {
+            if interior.contains(coord) && (around.contains(coord) || grid[*coord].entropy() > 0) {
+                grid[*coord] = initial[*coord].clone();
+            }
+        }
+        block *= 2;
+    }
+}
+
+fn translate(coord: (isize, isize, isize), by: (isize, isize, isize)) -> (isize, isize, isize) {
+    (coord.0 + by.0, coord.1 + by.1, coord.2 + by.2)
+}
+
+// Passes coordinates in a part of a grid to a rule as coordinates in the whole
+// grid
+struct Translated<'a, Rule> {
+    rule: &'a Rule,
+    origin: (isize, isize, isize),
+}
+
+impl<'a, S: State + 'static, Rule: CollapseRule<S, CubeGrid<S>>> CollapseRule<S, CubeGrid<S>>
+    for Translated<'a, Rule>
+{
+    fn neighbor_offsets(&self) -> Box<[(isize, isize, isize)]> {
+        self.rule.neighbor_offsets()
+    }
+
+    fn initialize(&self, coord: (isize, isize, isize), cell: &mut S) {
+        self.rule.initialize(translate(coord, self.origin), cell);
+    }
+
+    fn collapse(&self, coord: (isize, isize, isize), cell: &mut S, neighbors: &[Option<S>]) {
+        self.rule
+            .collapse(translate(coord, self.origin), cell, neighbors);
+    }
+
+    fn observe(
+        &self,
+        coord: (isize, isize, isize),
+        cell: &mut S,
+        neighbors: &[Option<S>],
+        rng: &mut dyn RngCore,
+    ) {
+        self.rule
+            .observe(translate(coord, self.origin), cell, neighbors, rng);
+    }
+}
+
+// Mixes the world seed with a chunk coordinate
+pub(crate) fn chunk_seed(seed: u64, chunk: ChunkCoordinate) -> u64 {
+    let mut hash = seed;
+    for component in [chunk.0, chunk.1, chunk.2] {
+        hash = (hash ^ component as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+        hash ^= hash >> 32;
+    }
+    hash
+}
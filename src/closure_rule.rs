@@ -0,0 +1,54 @@
+use rand::RngCore;
+
+use crate::set_rule::SetCollapseObserver;
+use crate::{CollapseRule, Space, State};
+
+/// A collapse rule defined by a closure, for rules which are quicker to write
+/// as code than as a table, e.g. "only water below y=1".
+///
+/// * `neighbor_offsets` - The neighbors passed to the closure, in order
+/// * `collapse_fn` - Narrows a cell given its coordinate and the states of its
+///   neighbors, as in [CollapseRule::collapse]
+/// * `observer` - Chooses the final state of observed cells
+pub struct ClosureRule<D, F, O> {
+    neighbor_offsets: Box<[D]>,
+    collapse_fn: F,
+    observer: O,
+}
+
+impl<D: Clone, F, O> ClosureRule<D, F, O> {
+    pub fn new(neighbor_offsets: &[D], collapse_fn: F, observer: O) -> Self {
+        Self {
+            neighbor_offsets: neighbor_offsets.into(),
+            collapse_fn,
+            observer,
+        }
+    }
+}
+
+impl<S, Sp, F, O> CollapseRule<S, Sp> for ClosureRule<Sp::CoordinateDelta, F, O>
+where
+    S: State,
+    Sp: Space<S>,
+    Sp::CoordinateDelta: Clone,
+    F: Fn(&Sp::Coordinate, &mut S, &[Option<S>]),
+    O: SetCollapseObserver<S, Sp::Coordinate>,
+{
+    fn neighbor_offsets(&self) -> Box<[Sp::CoordinateDelta]> {
+        self.neighbor_offsets.clone()
+    }
+
+    fn collapse(&self, coord: Sp::Coordinate, cell: &mut S, neighbors: &[Option<S>]) {
+        (self.collapse_fn)(&coord, cell, neighbors);
+    }
+
+    fn observe(
+        &self,
+        coord: Sp::Coordinate,
+        cell: &mut S,
+        neighbors: &[Option<S>],
+        rng: &mut dyn RngCore,
+    ) {
+        self.observer.observe(coord, cell, neighbors, rng);
+    }
+}
@@ -1,3 +1,5 @@
+use rand::RngCore;
+
 use crate::{Space, State};
 
 /// Collapse rules define the relationships between a cell's possible state
@@ -11,17 +13,52 @@ use crate::{Space, State};
 pub trait CollapseRule<S: State, Sp: 'static + Space<S>> {
     /// Neighbor directions are specified as a list of coordinate deltas.
     fn neighbor_offsets(&self) -> Box<[Sp::CoordinateDelta]>;
+    /// Narrows the initial states of a cell before the collapse starts, so
+    /// that restrictions which only depend on the cell's position are known
+    /// to propagation from the beginning. A [crate::Solver] initializes its
+    /// cells when it first propagates, leaving out cells outside the region it
+    /// is confined to.
+    ///
+    /// Does nothing by default.
+    fn initialize(&self, _coord: Sp::Coordinate, _cell: &mut S) {}
     /// The collapse rule, which modifies the possible states of 'cell' based
     /// on the states of neighboring cells.
     ///
+    /// * `coord` - The coordinate of the cell
     /// * `cell` - The cell state to modify
     /// * `neighbors` - The states of neighbors in the order specified by
-    /// `NEIGHBOR_DIRECTIONS`. `Some(<state>)` if the cell exists, and `None`
-    /// otherwise.
-    fn collapse(&self, cell: &mut S, neighbors: &[Option<S>]);
-    /// The observe rule, which forces a cell into a zero-entropy state.
+    ///   `NEIGHBOR_DIRECTIONS`. `Some(<state>)` if the cell exists, and `None`
+    ///   otherwise.
+    fn collapse(&self, coord: Sp::Coordinate, cell: &mut S, neighbors: &[Option<S>]);
+    /// The observe rule, which forces a cell into a zero-entropy state. A
+    /// cell which has no state left to choose is left as a contradiction.
     ///
+    /// * `coord` - The coordinate of the cell to observe
     /// * `cell` - The cell to observe
     /// * `neighbors` - The states of neighbor cells as in `collapse()` above.
-    fn observe(&self, cell: &mut S, neighbors: &[Option<S>]);
+    /// * `rng` - Source of randomness for choosing between states
+    fn observe(
+        &self,
+        coord: Sp::Coordinate,
+        cell: &mut S,
+        neighbors: &[Option<S>],
+        rng: &mut dyn RngCore,
+    );
+}
+
+// Adds the offsets of one rule to the offsets of a combination of rules,
+// returning where each of the rule's offsets ended up in the combination
+pub(crate) fn merge_offsets<D: PartialEq + Clone>(
+    merged: &mut Vec<D>,
+    offsets: &[D],
+) -> Box<[usize]> {
+    offsets
+        .iter()
+        .map(|offset| {
+            merged.iter().position(|o| o == offset).unwrap_or_else(|| {
+                merged.push(offset.clone());
+                merged.len() - 1
+            })
+        })
+        .collect()
 }
@@ -0,0 +1,158 @@
+use rand::RngCore;
+use std::marker::PhantomData;
+
+use crate::collapse_rule::merge_offsets;
+use crate::{CollapseRule, Region, Space, State};
+
+/// Which of the rules combined by an [AndRule] observes cells
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum ObserveWith {
+    #[default]
+    First,
+    Second,
+}
+
+/// A collapse rule which applies two rules to every cell. A state is only
+/// possible if both rules allow it.
+///
+/// The rules' neighbor offsets are merged, and each rule is given the states
+/// of its own neighbors in the order it expects them.
+pub struct AndRule<S, Sp: Space<S>, First, Second> {
+    first: First,
+    second: Second,
+    observe_with: ObserveWith,
+    neighbor_offsets: Box<[Sp::CoordinateDelta]>,
+    first_offsets: Box<[usize]>,
+    second_offsets: Box<[usize]>,
+    states: PhantomData<fn() -> S>,
+}
+
+impl<S: State, Sp: Space<S>, First: CollapseRule<S, Sp>, Second: CollapseRule<S, Sp>>
+    AndRule<S, Sp, First, Second>
+where
+    Sp::CoordinateDelta: PartialEq + Clone,
+{
+    pub fn new(first: First, second: Second) -> Self {
+        let mut neighbor_offsets = Vec::new();
+        let first_offsets = merge_offsets(&mut neighbor_offsets, &first.neighbor_offsets());
+        let second_offsets = merge_offsets(&mut neighbor_offsets, &second.neighbor_offsets());
+        Self {
+            first,
+            second,
+            observe_with: ObserveWith::First,
+            neighbor_offsets: neighbor_offsets.into_boxed_slice(),
+            first_offsets,
+            second_offsets,
+            states: PhantomData,
+        }
+    }
+
+    /// Chooses which rule observes cells. Defaults to the first rule.
+    pub fn observe_with(mut self, observe_with: ObserveWith) -> Self {
+        self.observe_with = observe_with;
+        self
+    }
+}
+
+// Calls `f` with the neighbors of a cell in the order given by `indices`.
+// Indices into the start of the neighbors in order, as they always are for
+// the first rule, are passed on without copying any states.
+fn with_neighbors<S: Clone, T>(
+    neighbors: &[Option<S>],
+    indices: &[usize],
+    f: impl FnOnce(&[Option<S>]) -> T,
+) -> T {
+    if indices.iter().enumerate().all(|(i, index)| i == *index) {
+        f(&neighbors[..indices.len()])
+    } else {
+        let reordered: Vec<Option<S>> = indices.iter().map(|i| neighbors[*i].clone()).collect();
+        f(&reordered)
+    }
+}
+
+impl<S: State, Sp: Space<S>, First: CollapseRule<S, Sp>, Second: CollapseRule<S, Sp>>
+    CollapseRule<S, Sp> for AndRule<S, Sp, First, Second>
+where
+    Sp::CoordinateDelta: Clone,
+{
+    fn neighbor_offsets(&self) -> Box<[Sp::CoordinateDelta]> {
+        self.neighbor_offsets.clone()
+    }
+
+    fn initialize(&self, coord: Sp::Coordinate, cell: &mut S) {
+        self.first.initialize(coord, cell);
+        self.second.initialize(coord, cell);
+    }
+
+    fn collapse(&self, coord: Sp::Coordinate, cell: &mut S, neighbors: &[Option<S>]) {
+        with_neighbors(neighbors, &self.first_offsets, |neighbors| {
+            self.first.collapse(coord, cell, neighbors)
+        });
+        with_neighbors(neighbors, &self.second_offsets, |neighbors| {
+            self.second.collapse(coord, cell, neighbors)
+        });
+    }
+
+    fn observe(
+        &self,
+        coord: Sp::Coordinate,
+        cell: &mut S,
+        neighbors: &[Option<S>],
+        rng: &mut dyn RngCore,
+    ) {
+        match self.observe_with {
+            ObserveWith::First => with_neighbors(neighbors, &self.first_offsets, |neighbors| {
+                self.first.observe(coord, cell, neighbors, rng)
+            }),
+            ObserveWith::Second => with_neighbors(neighbors, &self.second_offsets, |neighbors| {
+                self.second.observe(coord, cell, neighbors, rng)
+            }),
+        }
+    }
+}
+
+/// A collapse rule which only applies `rule` to cells inside `region`, and
+/// leaves other cells as they are. Usually combined with another rule using
+/// [AndRule], e.g. to restrict some states to a band of heights.
+///
+/// Observing is always left to `rule`, inside the region or not.
+pub struct ConditionalRule<Rule, Reg> {
+    pub rule: Rule,
+    pub region: Reg,
+}
+
+impl<Rule, Reg> ConditionalRule<Rule, Reg> {
+    pub fn new(rule: Rule, region: Reg) -> Self {
+        Self { rule, region }
+    }
+}
+
+impl<S: State, Sp: Space<S>, Rule: CollapseRule<S, Sp>, Reg: Region<Sp::Coordinate>>
+    CollapseRule<S, Sp> for ConditionalRule<Rule, Reg>
+{
+    fn neighbor_offsets(&self) -> Box<[Sp::CoordinateDelta]> {
+        self.rule.neighbor_offsets()
+    }
+
+    fn initialize(&self, coord: Sp::Coordinate, cell: &mut S) {
+        if self.region.contains(&coord) {
+            self.rule.initialize(coord, cell);
+        }
+    }
+
+    fn collapse(&self, coord: Sp::Coordinate, cell: &mut S, neighbors: &[Option<S>]) {
+        if self.region.contains(&coord) {
+            self.rule.collapse(coord, cell, neighbors);
+        }
+    }
+
+    fn observe(
+        &self,
+        coord: Sp::Coordinate,
+        cell: &mut S,
+        neighbors: &[Option<S>],
+        rng: &mut dyn RngCore,
+    ) {
+        self.rule.observe(coord, cell, neighbors, rng);
+    }
+}
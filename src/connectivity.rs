@@ -0,0 +1,250 @@
+use bevy_utils::{HashMap, HashSet};
+use std::collections::VecDeque;
+
+use crate::{Contradiction, GlobalConstraint, SetState, Space, State};
+
+/// Global constraint which requires every walkable cell to be part of a single
+/// connected region, such as keeping all rooms of a dungeon reachable from
+/// each other.
+///
+/// Cells are connected when they are both walkable and one is a neighbor of
+/// the other through `deltas`. Cells which can no longer be connected to the
+/// cells which are already walkable have the walkable states removed, cells
+/// which every connection between walkable cells passes through are forced to
+/// be walkable, and a contradiction is raised if walkable cells end up split
+/// apart.
+pub struct ConnectivityConstraint<S, C, D> {
+    walkable: S,
+    deltas: Box<[D]>,
+    // Cells with at least one walkable state remaining
+    possible: HashSet<C>,
+    // Cells with only walkable states remaining
+    definite: HashSet<C>,
+}
+
+impl<S: SetState + State, C, D> ConnectivityConstraint<S, C, D> {
+    /// * `walkable` - Every state which counts as walkable
+    /// * `deltas` - Offsets between cells which are connected to each other
+    pub fn new(walkable: &S, deltas: &[D]) -> Self
+    where
+        D: Clone,
+    {
+        Self {
+            walkable: walkable.clone(),
+            deltas: deltas.to_vec().into_boxed_slice(),
+            possible: HashSet::default(),
+            definite: HashSet::default(),
+        }
+    }
+}
+
+// Checks if every remaining state of `cell` is in `states`
+pub(crate) fn only_has<S: SetState + State>(cell: &S, states: &S) -> bool {
+    let mut rest = cell.clone();
+    rest.clear_states(states);
+    cell.has_any_of(cell) && !rest.has_any_of(cell)
+}
+
+impl<S: SetState + State, Sp: Space<S>> GlobalConstraint<S, Sp>
+    for ConnectivityConstraint<S, Sp::Coordinate, Sp::CoordinateDelta>
+{
+    fn propagate(
+        &mut self,
+        space: &mut Sp,
+        changed: &[Sp::Coordinate],
+        narrowed: &mut Vec<Sp::Coordinate>,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        // Connectivity only has to be rechecked when a cell can no longer be
+        // walkable, or has to be walkable
+        let mut dirty = false;
+        for coord in changed {
+            let cell = &space[*coord];
+            if !cell.has_any_of(&self.walkable) {
+                dirty |= self.possible.remove(coord);
+                self.definite.remove(coord);
+            } else {
+                self.possible.insert(*coord);
+                if only_has(cell, &self.walkable) {
+                    dirty |= self.definite.insert(*coord);
+                }
+            }
+        }
+        let Some(start) = self.definite.iter().min().copied() else {
+            return Ok(());
+        };
+        if !dirty {
+            return Ok(());
+        }
+
+        let reachable = flood(space, start, &self.deltas, |coord| {
+            self.possible.contains(coord)
+        });
+        if let Some(isolated) = self
+            .definite
+            .iter()
+            .filter(|coord| !reachable.contains(*coord))
+            .min()
+        {
+            return Err(Contradiction {
+                coordinate: *isolated,
+            });
+        }
+        for coord in self.possible.iter() {
+            if !reachable.contains(coord) {
+                space[*coord].clear_states(&self.walkable);
+                narrowed.push(*coord);
+            }
+        }
+        self.possible = reachable;
+
+        // Cells which every connection between walkable cells passes through
+        // have to be walkable themselves
+        let separators = separators(space, start, &self.deltas, &self.possible, |coord| {
+            self.definite.contains(coord)
+        });
+        for coord in separators {
+            space[coord].retain_states(&self.walkable);
+            narrowed.push(coord);
+        }
+        Ok(())
+    }
+}
+
+/// Finds the cells of the connected region `cells` which separate a required
+/// cell from `root` when removed, i.e. the cells which every path between
+/// `root` and some required cell passes through.
+pub(crate) fn separators<St, Sp: Space<St>>(
+    space: &Sp,
+    root: Sp::Coordinate,
+    deltas: &[Sp::CoordinateDelta],
+    cells: &HashSet<Sp::Coordinate>,
+    required: impl Fn(&Sp::Coordinate) -> bool,
+) -> Vec<Sp::Coordinate> {
+    let coords: Vec<Sp::Coordinate> = cells.iter().copied().collect();
+    let indices: HashMap<Sp::Coordinate, usize> = coords
+        .iter()
+        .enumerate()
+        .map(|(i, coord)| (*coord, i))
+        .collect();
+    let mut neighbors = vec![None; deltas.len()];
+    let adjacency: Vec<Vec<usize>> = coords
+        .iter()
+        .map(|coord| {
+            space.neighbors(*coord, deltas, &mut neighbors);
+            neighbors
+                .iter()
+                .flatten()
+                .filter_map(|neighbor| indices.get(neighbor).copied())
+                .collect()
+        })
+        .collect();
+
+    // Iterative depth-first search tracking the lowest discovery time reachable
+    // from each subtree, and the number of required cells within it
+    let unvisited = usize::MAX;
+    let mut discovered = vec![unvisited; coords.len()];
+    let mut low = vec![0; coords.len()];
+    let mut subtree_required = vec![0; coords.len()];
+    let mut is_separator = vec![false; coords.len()];
+    let root = indices[&root];
+    let mut time = 0;
+    let mut stack = vec![(root, unvisited, 0)];
+    discovered[root] = time;
+    low[root] = time;
+    while let Some((vertex, parent, next_edge)) = stack.last_mut() {
+        let (vertex, parent) = (*vertex, *parent);
+        if let Some(&next) = adjacency[vertex].get(*next_edge) {
+            *next_edge += 1;
+            if discovered[next] == unvisited {
+                time += 1;
+                discovered[next] = time;
+                low[next] = time;
+                stack.push((next, vertex, 0));
+            } else if next != parent {
+                low[vertex] = low[vertex].min(discovered[next]);
+            }
+        } else {
+            stack.pop();
+            if required(&coords[vertex]) {
+                subtree_required[vertex] += 1;
+            }
+            if parent != unvisited {
+                low[parent] = low[parent].min(low[vertex]);
+                subtree_required[parent] += subtree_required[vertex];
+                if parent != root
+                    && low[vertex] >= discovered[parent]
+                    && subtree_required[vertex] > 0
+                {
+                    is_separator[parent] = true;
+                }
+            }
+        }
+    }
+
+    let mut separators: Vec<Sp::Coordinate> = coords
+        .iter()
+        .zip(is_separator)
+        .filter(|(coord, is_separator)| *is_separator && !required(coord))
+        .map(|(coord, _)| *coord)
+        .collect();
+    separators.sort();
+    separators
+}
+
+// Finds every cell connected to `start` through cells accepted by `include`
+pub(crate) fn flood<St, Sp: Space<St>>(
+    space: &Sp,
+    start: Sp::Coordinate,
+    deltas: &[Sp::CoordinateDelta],
+    include: impl Fn(&Sp::Coordinate) -> bool,
+) -> HashSet<Sp::Coordinate> {
+    let mut reached = HashSet::default();
+    let mut to_visit = VecDeque::new();
+    let mut neighbors = vec![None; deltas.len()];
+    reached.insert(start);
+    to_visit.push_back(start);
+    while let Some(visiting) = to_visit.pop_front() {
+        space.neighbors(visiting, deltas, &mut neighbors);
+        for neighbor in neighbors.iter().flatten() {
+            if include(neighbor) && reached.insert(*neighbor) {
+                to_visit.push_back(*neighbor);
+            }
+        }
+    }
+    reached
+}
+
+/// Groups the resolved walkable cells of `space` into regions which are
+/// connected through `deltas`, largest first. A space satisfying a
+/// [ConnectivityConstraint] has at most one region.
+pub fn walkable_regions<S: SetState + State, Sp: Space<S>>(
+    space: &Sp,
+    walkable: &S,
+    deltas: &[Sp::CoordinateDelta],
+) -> Vec<Vec<Sp::Coordinate>> {
+    let walkable_cells: HashSet<Sp::Coordinate> = space
+        .coordinate_list()
+        .iter()
+        .filter(|coord| space[**coord].entropy() == 0 && space[**coord].has_any_of(walkable))
+        .copied()
+        .collect();
+    let mut remaining: Vec<Sp::Coordinate> = walkable_cells.iter().copied().collect();
+    remaining.sort();
+
+    let mut visited = HashSet::default();
+    let mut regions = Vec::new();
+    for coord in remaining {
+        if visited.contains(&coord) {
+            continue;
+        }
+        let mut region: Vec<Sp::Coordinate> =
+            flood(space, coord, deltas, |coord| walkable_cells.contains(coord))
+                .into_iter()
+                .collect();
+        region.sort();
+        visited.extend(region.iter().copied());
+        regions.push(region);
+    }
+    regions.sort_by_key(|region| std::cmp::Reverse(region.len()));
+    regions
+}
@@ -0,0 +1,126 @@
+use bevy_utils::HashSet;
+use std::ops::{Bound, RangeBounds};
+
+use crate::{Contradiction, GlobalConstraint, SetState, Space, State};
+
+/// Global constraint on the number of cells which resolve to a state, such as
+/// "exactly one boss room" or "at most 3 fountains".
+///
+/// Once the maximum is reached, the state is removed from every unresolved
+/// cell. When only as many cells as the minimum can still take on the state,
+/// those cells are forced into it, and having fewer is a contradiction.
+pub struct CountConstraint<S, C> {
+    state: S,
+    min: usize,
+    max: usize,
+    // Cells which can still resolve to the state, including resolved ones
+    candidates: HashSet<C>,
+    resolved: HashSet<C>,
+}
+
+impl<S: SetState + State, C> CountConstraint<S, C> {
+    /// * `state` - The final state to count
+    /// * `count` - The allowed number of cells in `state`, such as `1..=1` or
+    ///   `..=3`
+    pub fn new(state: &S, count: impl RangeBounds<usize>) -> Self {
+        let (min, max) = inclusive_bounds(count);
+        Self {
+            state: state.clone(),
+            min,
+            max,
+            candidates: HashSet::default(),
+            resolved: HashSet::default(),
+        }
+    }
+}
+
+// Converts a range into its inclusive minimum and maximum
+pub(crate) fn inclusive_bounds(range: impl RangeBounds<usize>) -> (usize, usize) {
+    let min = match range.start_bound() {
+        Bound::Included(min) => *min,
+        Bound::Excluded(min) => min + 1,
+        Bound::Unbounded => 0,
+    };
+    let max = match range.end_bound() {
+        Bound::Included(max) => *max,
+        Bound::Excluded(max) => max.saturating_sub(1),
+        Bound::Unbounded => usize::MAX,
+    };
+    (min, max)
+}
+
+impl<S: SetState + State, Sp: Space<S>> GlobalConstraint<S, Sp>
+    for CountConstraint<S, Sp::Coordinate>
+{
+    fn propagate(
+        &mut self,
+        space: &mut Sp,
+        changed: &[Sp::Coordinate],
+        narrowed: &mut Vec<Sp::Coordinate>,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        // The last cell which could no longer take on the state
+        let mut last_removed = None;
+        for coord in changed {
+            let cell = &space[*coord];
+            if cell.has_any_of(&self.state) {
+                self.candidates.insert(*coord);
+                if cell.entropy() == 0 {
+                    self.resolved.insert(*coord);
+                }
+            } else {
+                if self.candidates.remove(coord) {
+                    last_removed = Some(*coord);
+                }
+                self.resolved.remove(coord);
+            }
+        }
+
+        if self.resolved.len() > self.max {
+            // Blame the most recently resolved cell
+            let coordinate = changed
+                .iter()
+                .rev()
+                .find(|coord| self.resolved.contains(coord))
+                .or_else(|| self.resolved.iter().next());
+            if let Some(coordinate) = coordinate {
+                return Err(Contradiction {
+                    coordinate: *coordinate,
+                });
+            }
+        }
+        if self.candidates.len() < self.min {
+            if let Some(coordinate) = last_removed.or_else(|| changed.first().copied()) {
+                return Err(Contradiction { coordinate });
+            }
+        }
+
+        if self.candidates.len() == self.resolved.len() {
+            return Ok(());
+        }
+        if self.resolved.len() == self.max {
+            for coord in self.candidates.difference(&self.resolved) {
+                space[*coord].clear_states(&self.state);
+                narrowed.push(*coord);
+            }
+            self.candidates.clone_from(&self.resolved);
+        } else if self.candidates.len() == self.min {
+            for coord in self.candidates.iter() {
+                if self.resolved.contains(coord) {
+                    continue;
+                }
+                let cell = &mut space[*coord];
+                let entropy = cell.entropy();
+                cell.retain_states(&self.state);
+                if cell.entropy() < entropy {
+                    narrowed.push(*coord);
+                }
+                // The state may be a set of several final states, in which case
+                // the cell still has to resolve
+                if cell.entropy() == 0 {
+                    self.resolved.insert(*coord);
+                }
+            }
+        }
+        Ok(())
+    }
+}
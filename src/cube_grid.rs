@@ -1,4 +1,6 @@
-use crate::{InvertDelta, Space};
+use crate::{InvertDelta, Region, Space};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::ops::{Index, IndexMut};
 
 /*
@@ -11,12 +13,59 @@ Cube as viewed from above
      (0,0,0) - - - - (width,0,0)
 */
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(try_from = "RawCubeGrid<T>")
+)]
 pub struct CubeGrid<T> {
     cells: Box<[T]>,
     width: isize,
     length: isize,
     height: isize,
+    // Whether the x, y and z axes wrap around
+    wrap: (bool, bool, bool),
+}
+
+// A deserialized grid, before checking that its cells fill its dimensions
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawCubeGrid<T> {
+    cells: Box<[T]>,
+    width: isize,
+    length: isize,
+    height: isize,
+    #[serde(default)]
+    wrap: (bool, bool, bool),
+}
+
+#[cfg(feature = "serde")]
+impl<T> TryFrom<RawCubeGrid<T>> for CubeGrid<T> {
+    type Error = String;
+
+    fn try_from(raw: RawCubeGrid<T>) -> Result<Self, Self::Error> {
+        let (width, length, height) = (raw.width, raw.length, raw.height);
+        let Some(size) = grid_size(width, length, height) else {
+            return Err(format!("invalid grid size {}x{}x{}", width, length, height));
+        };
+        if raw.cells.len() != size {
+            return Err(format!(
+                "{} cells don't fill a {}x{}x{} grid",
+                raw.cells.len(),
+                width,
+                length,
+                height
+            ));
+        }
+        Ok(Self {
+            cells: raw.cells,
+            width,
+            length,
+            height,
+            wrap: raw.wrap,
+        })
+    }
 }
 
 impl InvertDelta for (isize, isize, isize) {
@@ -50,8 +99,89 @@ impl<T> CubeGrid<T> {
             width,
             length,
             height,
+            wrap: (false, false, false),
+        }
+    }
+
+    /// Makes the grid wrap around along the given axes, so that the cells on
+    /// opposite faces are neighbors, e.g. for tiling textures.
+    pub fn wrapping(mut self, x: bool, y: bool, z: bool) -> Self {
+        self.wrap = (x, y, z);
+        self
+    }
+
+    // Whether the x, y and z axes wrap around
+    pub fn wrapped_axes(&self) -> (bool, bool, bool) {
+        self.wrap
+    }
+
+    pub fn width(&self) -> isize {
+        self.width
+    }
+
+    pub fn length(&self) -> isize {
+        self.length
+    }
+
+    pub fn height(&self) -> isize {
+        self.height
+    }
+
+    // Whether the coordinate lies inside the grid
+    pub fn in_bounds(&self, coord: (isize, isize, isize)) -> bool {
+        let (x, y, z) = coord;
+        (0..self.width).contains(&x)
+            && (0..self.height).contains(&y)
+            && (0..self.length).contains(&z)
+    }
+}
+
+// The number of cells in a grid, or `None` if a dimension is negative or the
+// number doesn't fit in a `usize`
+pub(crate) fn grid_size(width: isize, length: isize, height: isize) -> Option<usize> {
+    let [width, length, height] = [width, length, height].map(|d| usize::try_from(d).ok());
+    width?.checked_mul(length?)?.checked_mul(height?)
+}
+
+/// Every offset within `radius` steps of a cell along the grid axes, not
+/// including the cell itself
+pub fn manhattan_offsets(radius: isize) -> Vec<(isize, isize, isize)> {
+    let mut offsets = Vec::new();
+    for dy in -radius..=radius {
+        for dz in -radius..=radius {
+            for dx in -radius..=radius {
+                let distance = dx.abs() + dy.abs() + dz.abs();
+                if distance != 0 && distance <= radius {
+                    offsets.push((dx, dy, dz));
+                }
+            }
         }
     }
+    offsets
+}
+
+/// An axis aligned box of cells in a [CubeGrid], from `min` up to but not
+/// including `max`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CubeBox {
+    pub min: (isize, isize, isize),
+    pub max: (isize, isize, isize),
+}
+
+impl Region<(isize, isize, isize)> for CubeBox {
+    fn contains(&self, coord: &(isize, isize, isize)) -> bool {
+        let (x, y, z) = *coord;
+        (self.min.0..self.max.0).contains(&x)
+            && (self.min.1..self.max.1).contains(&y)
+            && (self.min.2..self.max.2).contains(&z)
+    }
+}
+
+// A grid of booleans can be used as a mask, containing the cells set to true
+impl Region<(isize, isize, isize)> for CubeGrid<bool> {
+    fn contains(&self, coord: &(isize, isize, isize)) -> bool {
+        self.in_bounds(*coord) && self[*coord]
+    }
 }
 
 // Access to a certain cells possible states
@@ -106,11 +236,17 @@ impl<T: 'static> Space<T> for CubeGrid<T> {
         let (x, y, z) = coord;
         for i in 0..neighbor_directions.len() {
             let (dx, dy, dz) = neighbor_directions[i];
-            let (nx, ny, nz) = (x + dx, y + dy, z + dz);
-            if nx.clamp(0, self.width - 1) == nx
-                && nz.clamp(0, self.length - 1) == nz
-                && ny.clamp(0, self.height - 1) == ny
-            {
+            let (mut nx, mut ny, mut nz) = (x + dx, y + dy, z + dz);
+            if self.wrap.0 {
+                nx = nx.rem_euclid(self.width);
+            }
+            if self.wrap.1 {
+                ny = ny.rem_euclid(self.height);
+            }
+            if self.wrap.2 {
+                nz = nz.rem_euclid(self.length);
+            }
+            if self.in_bounds((nx, ny, nz)) {
                 neighbors[i] = Some((nx, ny, nz));
             } else {
                 neighbors[i] = None;
@@ -0,0 +1,256 @@
+use bevy_utils::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::{Contradiction, GlobalConstraint, InvertDelta, SetState, Space, State};
+
+/// Global constraint keeping a state away from another one, such as "no two
+/// towers within 4 cells of each other".
+///
+/// Whenever a cell resolves to `near`, `excluded` is removed from every cell
+/// at one of the offsets from it. Likewise, when a cell resolves to `excluded`,
+/// `near` is removed from the cells it would be at an offset from. Cells are
+/// only looked at once they resolve, so the constraint stays cheap regardless
+/// of how large the offsets are.
+pub struct ExclusionConstraint<S, C, D> {
+    near: S,
+    excluded: S,
+    offsets: Box<[D]>,
+    inverted: Box<[D]>,
+    // Resolved cells which have already been handled
+    seen: HashSet<C>,
+    neighbors: Vec<Option<C>>,
+}
+
+impl<S: SetState + State, C: Clone, D: Clone + InvertDelta> ExclusionConstraint<S, C, D> {
+    /// * `near` - The state which excludes the other one around it
+    /// * `excluded` - The states which may not appear around `near`. Use the
+    ///   same state as `near` to keep cells of a state apart.
+    /// * `offsets` - The cells around `near` which are affected, such as
+    ///   [crate::cube_grid::manhattan_offsets]
+    pub fn new(near: &S, excluded: &S, offsets: &[D]) -> Self {
+        Self {
+            near: near.clone(),
+            excluded: excluded.clone(),
+            offsets: offsets.into(),
+            inverted: offsets.iter().map(|offset| offset.invert_delta()).collect(),
+            seen: HashSet::default(),
+            neighbors: vec![None; offsets.len()],
+        }
+    }
+}
+
+// Removes `states` from the cells at `offsets` from `coord`
+fn clear_around<S: SetState + State, Sp: Space<S>>(
+    space: &mut Sp,
+    coord: Sp::Coordinate,
+    offsets: &[Sp::CoordinateDelta],
+    states: &S,
+    neighbors: &mut [Option<Sp::Coordinate>],
+    narrowed: &mut Vec<Sp::Coordinate>,
+) -> Result<(), Contradiction<Sp::Coordinate>> {
+    space.neighbors(coord, offsets, neighbors);
+    for neighbor in neighbors.iter().flatten() {
+        let cell = &mut space[*neighbor];
+        if !cell.has_any_of(states) {
+            continue;
+        }
+        if cell.entropy() == 0 {
+            return Err(Contradiction {
+                coordinate: *neighbor,
+            });
+        }
+        cell.clear_states(states);
+        narrowed.push(*neighbor);
+    }
+    Ok(())
+}
+
+impl<S: SetState + State, Sp: Space<S>> GlobalConstraint<S, Sp>
+    for ExclusionConstraint<S, Sp::Coordinate, Sp::CoordinateDelta>
+{
+    fn propagate(
+        &mut self,
+        space: &mut Sp,
+        changed: &[Sp::Coordinate],
+        narrowed: &mut Vec<Sp::Coordinate>,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        for coord in changed {
+            let cell = &space[*coord];
+            if cell.entropy() != 0 {
+                continue;
+            }
+            let is_near = cell.has_any_of(&self.near);
+            let is_excluded = cell.has_any_of(&self.excluded);
+            if !(is_near || is_excluded) || !self.seen.insert(*coord) {
+                continue;
+            }
+            let neighbors = &mut self.neighbors;
+            if is_near {
+                clear_around(
+                    space,
+                    *coord,
+                    &self.offsets,
+                    &self.excluded,
+                    neighbors,
+                    narrowed,
+                )?;
+            }
+            if is_excluded {
+                clear_around(
+                    space,
+                    *coord,
+                    &self.inverted,
+                    &self.near,
+                    neighbors,
+                    narrowed,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Global constraint requiring a state near another one, such as "a lamp
+/// within 3 cells of every bench".
+///
+/// Whenever a cell resolves to `state`, the cells at the offsets from it are
+/// watched until one of them resolves to `required`. Only changes to watched
+/// cells cause the requirement to be checked again. Once a single watched cell
+/// can still take on `required`, it is forced into it.
+///
+/// Cells which could still take on `state` lose it once no cell at the offsets
+/// from them can take on `required` any more.
+pub struct RequirementConstraint<S, C, D> {
+    state: S,
+    required: S,
+    offsets: Box<[D]>,
+    inverted: Box<[D]>,
+    seen: HashSet<C>,
+    // The unsatisfied cells watching each cell around them
+    watchers: HashMap<C, Vec<C>>,
+    neighbors: Vec<Option<C>>,
+}
+
+impl<S: SetState + State, C: Clone, D: Clone + InvertDelta> RequirementConstraint<S, C, D> {
+    /// * `state` - The state which needs `required` around it
+    /// * `required` - The states which have to appear around `state`
+    /// * `offsets` - The cells around `state` which may satisfy it, such as
+    ///   [crate::cube_grid::manhattan_offsets]
+    pub fn new(state: &S, required: &S, offsets: &[D]) -> Self {
+        Self {
+            state: state.clone(),
+            required: required.clone(),
+            offsets: offsets.into(),
+            inverted: offsets.iter().map(|offset| offset.invert_delta()).collect(),
+            seen: HashSet::default(),
+            watchers: HashMap::default(),
+            neighbors: vec![None; offsets.len()],
+        }
+    }
+}
+
+impl<S: SetState + State, C: Copy + Eq + Hash, D> RequirementConstraint<S, C, D> {
+    // Checks whether `coord` can still be satisfied, forcing the last cell
+    // which could satisfy it. Returns whether it still needs watching.
+    fn check<Sp: Space<S, Coordinate = C, CoordinateDelta = D>>(
+        &mut self,
+        space: &mut Sp,
+        coord: C,
+        narrowed: &mut Vec<C>,
+    ) -> Result<bool, Contradiction<C>> {
+        space.neighbors(coord, &self.offsets, &mut self.neighbors);
+        let mut candidates = self
+            .neighbors
+            .iter()
+            .flatten()
+            .filter(|neighbor| space[**neighbor].has_any_of(&self.required));
+        let Some(candidate) = candidates.next().copied() else {
+            return Err(Contradiction { coordinate: coord });
+        };
+        if candidates.next().is_some() {
+            let satisfied = self.neighbors.iter().flatten().any(|neighbor| {
+                let cell = &space[*neighbor];
+                cell.entropy() == 0 && cell.has_any_of(&self.required)
+            });
+            return Ok(!satisfied);
+        }
+        let cell = &mut space[candidate];
+        if cell.entropy() != 0 {
+            cell.retain_states(&self.required);
+            narrowed.push(candidate);
+        }
+        Ok(false)
+    }
+
+    // Removes `state` from `coord` if it is unresolved and no cell around it
+    // can take on `required` any more
+    fn prune<Sp: Space<S, Coordinate = C, CoordinateDelta = D>>(
+        &mut self,
+        space: &mut Sp,
+        coord: C,
+        narrowed: &mut Vec<C>,
+    ) {
+        let cell = &space[coord];
+        if cell.entropy() == 0 || !cell.has_any_of(&self.state) {
+            return;
+        }
+        space.neighbors(coord, &self.offsets, &mut self.neighbors);
+        let reachable = self
+            .neighbors
+            .iter()
+            .flatten()
+            .any(|neighbor| space[*neighbor].has_any_of(&self.required));
+        if !reachable {
+            space[coord].clear_states(&self.state);
+            narrowed.push(coord);
+        }
+    }
+}
+
+impl<S: SetState + State, Sp: Space<S>> GlobalConstraint<S, Sp>
+    for RequirementConstraint<S, Sp::Coordinate, Sp::CoordinateDelta>
+{
+    fn propagate(
+        &mut self,
+        space: &mut Sp,
+        changed: &[Sp::Coordinate],
+        narrowed: &mut Vec<Sp::Coordinate>,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        let mut to_check = Vec::new();
+        let mut to_prune = Vec::new();
+        for coord in changed {
+            if let Some(watchers) = self.watchers.get(coord) {
+                to_check.extend(watchers.iter().copied());
+            }
+            let cell = &space[*coord];
+            if cell.entropy() == 0 && cell.has_any_of(&self.state) && self.seen.insert(*coord) {
+                to_check.push(*coord);
+            }
+            // The cells which could be satisfied by this one
+            to_prune.push(*coord);
+            if !cell.has_any_of(&self.required) {
+                space.neighbors(*coord, &self.inverted, &mut self.neighbors);
+                to_prune.extend(self.neighbors.iter().flatten());
+            }
+        }
+        for coord in to_prune {
+            self.prune(space, coord, narrowed);
+        }
+
+        for coord in to_check {
+            let watching = self.check(space, coord, narrowed)?;
+            for neighbor in self.neighbors.iter().flatten() {
+                let watchers = self.watchers.entry(*neighbor).or_default();
+                let index = watchers.iter().position(|watcher| *watcher == coord);
+                match (watching, index) {
+                    (true, None) => watchers.push(coord),
+                    (false, Some(index)) => {
+                        watchers.swap_remove(index);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
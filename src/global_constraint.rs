@@ -0,0 +1,26 @@
+use crate::{Contradiction, Space, State};
+
+/// Global constraints restrict the space as a whole, in ways which can't be
+/// expressed through the neighbor relations of a [crate::CollapseRule], such
+/// as limiting how many times a state may appear.
+///
+/// Constraints are added to a [crate::Solver], which runs them whenever the
+/// collapse rule has finished propagating. Like collapse rules, constraints
+/// should only ever remove possible states from cells.
+pub trait GlobalConstraint<St: State, Sp: Space<St>> {
+    /// Narrows the cells of `space` as required by the constraint.
+    ///
+    /// * `space` - The space being collapsed
+    /// * `changed` - Cells which have narrowed since the constraint was last
+    ///   run. When the constraint is first added, this is every cell.
+    /// * `narrowed` - Output list of the cells narrowed by the constraint, which
+    ///   are then propagated by the collapse rule.
+    ///
+    /// Fails when the constraint can no longer be satisfied.
+    fn propagate(
+        &mut self,
+        space: &mut Sp,
+        changed: &[Sp::Coordinate],
+        narrowed: &mut Vec<Sp::Coordinate>,
+    ) -> Result<(), Contradiction<Sp::Coordinate>>;
+}
@@ -1,5 +1,7 @@
-use bevy_utils::HashSet;
-use std::hash::Hash;
+use bevy_utils::{FixedState, HashSet};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::hash::{BuildHasher, Hash};
 
 use crate::{Final, SetState, State};
 
@@ -7,6 +9,7 @@ use crate::{Final, SetState, State};
 ///
 /// * `T` - The underlying unique state identifier
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct HashsetState<T: Eq + Hash> {
     pub hashset: HashSet<T>,
 }
@@ -38,6 +41,10 @@ impl<T: Clone + Eq + Hash> State for HashsetState<T> {
         }
         self.hashset.len() as u32 - 1
     }
+
+    fn is_contradiction(&self) -> bool {
+        self.hashset.is_empty()
+    }
 }
 
 impl<T: Clone + Eq + Hash> SetState for HashsetState<T> {
@@ -55,13 +62,13 @@ impl<T: Clone + Eq + Hash> SetState for HashsetState<T> {
         }
     }
 
+    // Final states are collected in an order which only depends on the states
+    // themselves, rather than on the history of the hash set, so that runs
+    // with the same random seed give the same result
     fn collect_final_states(&self, states: &mut Vec<Self>) {
-        self.hashset
-            .iter()
-            .map(|x| {
-                states.push(Self::new_final(x));
-            })
-            .for_each(drop);
+        let mut finals: Vec<&T> = self.hashset.iter().collect();
+        finals.sort_by_cached_key(|x| FixedState.hash_one(x));
+        states.extend(finals.into_iter().map(Self::new_final));
     }
 }
 
@@ -0,0 +1,159 @@
+use rand::{Rng, RngCore};
+use std::fmt;
+
+use crate::cube_grid::CubeGrid;
+use crate::{CollapseRule, Contradiction, SetState, Solver, State};
+
+/// A collapsed cell of the coarse layer, passed to the mapping which builds
+/// the fine layer
+///
+/// * `coord` - The coordinate of the cell in the coarse layer
+/// * `state` - The collapsed state of the cell
+pub struct CoarseCell<'a, C> {
+    pub coord: (isize, isize, isize),
+    pub state: &'a C,
+    grid: &'a CubeGrid<C>,
+}
+
+impl<'a, C: 'static> CoarseCell<'a, C> {
+    /// The state of the coarse cell at `delta` from this one, if it exists.
+    /// Lets the fine layer agree with the coarse layer across the border
+    /// between two coarse cells, e.g. by placing a door between two rooms.
+    pub fn neighbor(&self, delta: (isize, isize, isize)) -> Option<&'a C> {
+        let (x, y, z) = self.coord;
+        let coord = (x + delta.0, y + delta.1, z + delta.2);
+        self.grid.in_bounds(coord).then(|| &self.grid[coord])
+    }
+}
+
+/// Error produced by [collapse_hierarchical], naming the layer in which the
+/// contradiction happened
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LayerContradiction {
+    Coarse(Contradiction<(isize, isize, isize)>),
+    Fine(Contradiction<(isize, isize, isize)>),
+}
+
+impl fmt::Display for LayerContradiction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Coarse(contradiction) => write!(f, "coarse layer: {}", contradiction),
+            Self::Fine(contradiction) => write!(f, "fine layer: {}", contradiction),
+        }
+    }
+}
+
+impl std::error::Error for LayerContradiction {}
+
+/// Perform the wave function collapse algorithm in two layers: a coarse
+/// layout first, and then the details inside each of its cells.
+///
+/// * `coarse` - The coarse layer, which is collapsed in-place
+/// * `coarse_rule` - The collapse rule for the coarse layer
+/// * `fine_rule` - The collapse rule for the fine layer
+/// * `scale` - Each coarse cell expands to a block of `scale`×`scale`×`scale`
+///   fine cells
+/// * `mapping` - Gives the states a fine cell may take on, from the coarse
+///   cell it lies in and its position within that cell's block
+/// * `rng` - Source of randomness for both layers
+///
+/// Fine cells start out in the states given by the mapping, and are kept
+/// within them while the fine layer is collapsed, whatever the fine rule does.
+/// The collapsed fine layer is checked against the mapping before it is
+/// returned, failing with a fine contradiction at the first cell outside it.
+///
+/// Returns the collapsed fine layer.
+pub fn collapse_hierarchical<C, F, CoarseRule, FineRule, R>(
+    coarse: &mut CubeGrid<C>,
+    coarse_rule: &CoarseRule,
+    fine_rule: &FineRule,
+    scale: isize,
+    mapping: impl Fn(&CoarseCell<C>, (isize, isize, isize)) -> F,
+    rng: &mut R,
+) -> Result<CubeGrid<F>, LayerContradiction>
+where
+    C: State + 'static,
+    F: State + SetState + 'static,
+    CoarseRule: CollapseRule<C, CubeGrid<C>>,
+    FineRule: CollapseRule<F, CubeGrid<F>>,
+    R: Rng,
+{
+    Solver::with_rng(&mut *coarse, coarse_rule, &mut *rng)
+        .and_then(|mut solver| solver.run())
+        .map_err(LayerContradiction::Coarse)?;
+
+    let coarse = &*coarse;
+    let layout = CubeGrid::new(
+        coarse.width() * scale,
+        coarse.length() * scale,
+        coarse.height() * scale,
+        |x, y, z| {
+            let coord = (x / scale, y / scale, z / scale);
+            let cell = CoarseCell {
+                coord,
+                state: &coarse[coord],
+                grid: coarse,
+            };
+            mapping(&cell, (x % scale, y % scale, z % scale))
+        },
+    );
+    let mut fine = layout.clone();
+    let rule = WithinLayout {
+        rule: fine_rule,
+        layout: &layout,
+    };
+    Solver::with_rng(&mut fine, &rule, &mut *rng)
+        .and_then(|mut solver| solver.run())
+        .map_err(LayerContradiction::Fine)?;
+
+    for y in 0..fine.height() {
+        for z in 0..fine.length() {
+            for x in 0..fine.width() {
+                let cell = &fine[(x, y, z)];
+                if cell.entropy() != 0 || !cell.has_any_of(&layout[(x, y, z)]) {
+                    let coordinate = (x, y, z);
+                    return Err(LayerContradiction::Fine(Contradiction { coordinate }));
+                }
+            }
+        }
+    }
+    Ok(fine)
+}
+
+// Keeps the cells of the fine layer within the states the coarse layer maps
+// them to
+struct WithinLayout<'a, Rule, F> {
+    rule: &'a Rule,
+    layout: &'a CubeGrid<F>,
+}
+
+impl<'a, F, Rule> CollapseRule<F, CubeGrid<F>> for WithinLayout<'a, Rule, F>
+where
+    F: State + SetState + 'static,
+    Rule: CollapseRule<F, CubeGrid<F>>,
+{
+    fn neighbor_offsets(&self) -> Box<[(isize, isize, isize)]> {
+        self.rule.neighbor_offsets()
+    }
+
+    fn initialize(&self, coord: (isize, isize, isize), cell: &mut F) {
+        self.rule.initialize(coord, cell);
+        cell.retain_states(&self.layout[coord]);
+    }
+
+    fn collapse(&self, coord: (isize, isize, isize), cell: &mut F, neighbors: &[Option<F>]) {
+        self.rule.collapse(coord, cell, neighbors);
+        cell.retain_states(&self.layout[coord]);
+    }
+
+    fn observe(
+        &self,
+        coord: (isize, isize, isize),
+        cell: &mut F,
+        neighbors: &[Option<F>],
+        rng: &mut dyn RngCore,
+    ) {
+        self.rule.observe(coord, cell, neighbors, rng);
+        cell.retain_states(&self.layout[coord]);
+    }
+}
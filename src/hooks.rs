@@ -0,0 +1,41 @@
+use crate::{Contradiction, Space, State};
+
+/// Callbacks for following the progress of a [crate::Solver], such as for
+/// drawing progress bars or debug overlays. Every callback does nothing by
+/// default.
+pub trait CollapseHooks<St: State, Sp: Space<St>> {
+    /// A cell was observed and forced into `state`
+    fn observed(&mut self, _coord: Sp::Coordinate, _state: &St) {}
+    /// A cell's possible states were narrowed by the collapse rule while
+    /// propagating
+    fn narrowed(&mut self, _coord: Sp::Coordinate, _entropy_before: u32, _entropy_after: u32) {}
+    /// A cell was left without any possible states
+    fn contradiction(&mut self, _contradiction: &Contradiction<Sp::Coordinate>) {}
+    /// Every cell in the space is resolved
+    fn completed(&mut self) {}
+    /// Called before each observation with the number of resolved cells and
+    /// the total number of cells in the space
+    fn progress(&mut self, _resolved: usize, _total: usize) {}
+}
+
+impl<St: State, Sp: Space<St>, H: CollapseHooks<St, Sp> + ?Sized> CollapseHooks<St, Sp> for &mut H {
+    fn observed(&mut self, coord: Sp::Coordinate, state: &St) {
+        (**self).observed(coord, state)
+    }
+
+    fn narrowed(&mut self, coord: Sp::Coordinate, entropy_before: u32, entropy_after: u32) {
+        (**self).narrowed(coord, entropy_before, entropy_after)
+    }
+
+    fn contradiction(&mut self, contradiction: &Contradiction<Sp::Coordinate>) {
+        (**self).contradiction(contradiction)
+    }
+
+    fn completed(&mut self) {
+        (**self).completed()
+    }
+
+    fn progress(&mut self, resolved: usize, total: usize) {
+        (**self).progress(resolved, total)
+    }
+}
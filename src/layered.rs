@@ -0,0 +1,315 @@
+use rand::RngCore;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+use std::marker::PhantomData;
+
+use crate::collapse_rule::merge_offsets;
+use crate::set_rule::choose_weighted;
+use crate::{CollapseRule, Final, SetState, Space, State};
+
+/// A cell state made up of two layers, such as a terrain tile and the
+/// decoration placed on it. Each layer is a set of states of its own.
+///
+/// As a [SetState], a pair stands for every combination of a state of the
+/// first layer with a state of the second, so pairs can be layered again for
+/// more than two layers. Combining or removing states can give a set of
+/// combinations which a pair can't represent, in which case the pair keeps a
+/// superset of it: adding states adds them to each layer, and removing states
+/// only takes them out of a layer when the other layer is covered entirely.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PairState<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+impl<A, B> PairState<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+}
+
+impl<A: State, B: State> State for PairState<A, B> {
+    // Grows with the number of combinations of the layers' states, and is only
+    // zero once both layers are final
+    fn entropy(&self) -> u32 {
+        (self.first.entropy() + 1)
+            .saturating_mul(self.second.entropy() + 1)
+            .saturating_sub(1)
+    }
+
+    fn is_contradiction(&self) -> bool {
+        self.first.is_contradiction() || self.second.is_contradiction()
+    }
+}
+
+impl<A: SetState + Clone, B: SetState + Clone> SetState for PairState<A, B> {
+    fn set_states(&mut self, states: &Self) {
+        self.first.set_states(&states.first);
+        self.second.set_states(&states.second);
+    }
+
+    fn has_any_of(&self, states: &Self) -> bool {
+        self.first.has_any_of(&states.first) && self.second.has_any_of(&states.second)
+    }
+
+    fn clear_states(&mut self, states: &Self) {
+        if !self.has_any_of(states) {
+            return;
+        }
+        if covers(&states.first, &self.first) {
+            self.second.clear_states(&states.second);
+        } else if covers(&states.second, &self.second) {
+            self.first.clear_states(&states.first);
+        }
+    }
+
+    fn retain_states(&mut self, states: &Self) {
+        self.first.retain_states(&states.first);
+        self.second.retain_states(&states.second);
+    }
+
+    fn collect_final_states(&self, states: &mut Vec<Self>) {
+        let mut firsts = Vec::new();
+        self.first.collect_final_states(&mut firsts);
+        let mut seconds = Vec::new();
+        self.second.collect_final_states(&mut seconds);
+        for first in firsts.iter() {
+            for second in seconds.iter() {
+                states.push(Self::new(first.clone(), second.clone()));
+            }
+        }
+    }
+}
+
+// Whether every state of `states` is in `covering`
+fn covers<S: SetState + Clone>(covering: &S, states: &S) -> bool {
+    let mut uncovered = states.clone();
+    uncovered.clear_states(covering);
+    let mut finals = Vec::new();
+    uncovered.collect_final_states(&mut finals);
+    finals.is_empty()
+}
+
+impl<TA, TB, A, B> Final<(TA, TB)> for PairState<A, B>
+where
+    TA: Eq + Hash + Clone,
+    TB: Eq + Hash + Clone,
+    A: Final<TA>,
+    B: Final<TB>,
+{
+    fn get(&self) -> Option<(TA, TB)> {
+        Some((self.first.get()?, self.second.get()?))
+    }
+}
+
+type PairWeight<A, B> = Box<dyn Fn(&A, &B) -> u32 + Send + Sync>;
+
+/// A collapse rule for [PairState] cells, which collapses each layer with its
+/// own rule and keeps the two layers of a cell compatible with each other.
+///
+/// Compatibility between the layers is given with [LayeredRule::compatible].
+/// Without any compatibility entries, every combination is allowed. Once there
+/// are some, states of the first layer without entries can't be combined with
+/// anything.
+///
+/// Observing a cell picks a compatible combination of final states for both
+/// layers at once, among those the layers' rules accept, weighted by
+/// [LayeredRule::weights]. The observers of the layers' rules aren't used.
+/// When no combination is left, the cell is left as a contradiction.
+///
+/// * `SpA`, `SpB` - The space types the layers' rules were built for. They
+///   only need to share their coordinate types with the layered space.
+pub struct LayeredRule<A, B, RuleA, RuleB, SpA: Space<A>, SpB> {
+    first_rule: RuleA,
+    second_rule: RuleB,
+    neighbor_offsets: Box<[SpA::CoordinateDelta]>,
+    first_offsets: Box<[usize]>,
+    second_offsets: Box<[usize]>,
+    compatible: Vec<(A, B)>,
+    weight: PairWeight<A, B>,
+    spaces: PhantomData<fn() -> (SpA, SpB)>,
+}
+
+impl<A, B, RuleA, RuleB, SpA, SpB> LayeredRule<A, B, RuleA, RuleB, SpA, SpB>
+where
+    A: SetState + State,
+    B: SetState + State,
+    SpA: Space<A>,
+    SpB: Space<B, CoordinateDelta = SpA::CoordinateDelta>,
+    SpA::CoordinateDelta: PartialEq + Clone,
+    RuleA: CollapseRule<A, SpA>,
+    RuleB: CollapseRule<B, SpB>,
+{
+    pub fn new(first_rule: RuleA, second_rule: RuleB) -> Self {
+        let mut neighbor_offsets = Vec::new();
+        let first_offsets = merge_offsets(&mut neighbor_offsets, &first_rule.neighbor_offsets());
+        let second_offsets = merge_offsets(&mut neighbor_offsets, &second_rule.neighbor_offsets());
+        Self {
+            first_rule,
+            second_rule,
+            neighbor_offsets: neighbor_offsets.into_boxed_slice(),
+            first_offsets,
+            second_offsets,
+            compatible: Vec::new(),
+            weight: Box::new(|_, _| 1),
+            spaces: PhantomData,
+        }
+    }
+
+    /// Allows the final states in `first` to be combined with the states in
+    /// `second` within the same cell
+    pub fn compatible(mut self, first: &A, second: &B) -> Self {
+        let mut states = Vec::new();
+        first.collect_final_states(&mut states);
+        for state in states {
+            match self.compatible.iter_mut().find(|(s, _)| *s == state) {
+                Some((_, allowed)) => allowed.set_states(second),
+                None => self.compatible.push((state, second.clone())),
+            }
+        }
+        self
+    }
+
+    /// Sets the weight of each combination of final states when observing.
+    /// Defaults to the same weight for every combination.
+    pub fn weights(mut self, weight_fn: impl Fn(&A, &B) -> u32 + Send + Sync + 'static) -> Self {
+        self.weight = Box::new(weight_fn);
+        self
+    }
+
+    fn is_compatible(&self, first: &A, second: &B) -> bool {
+        self.compatible.is_empty()
+            || self
+                .compatible
+                .iter()
+                .any(|(state, allowed)| first.has_any_of(state) && second.has_any_of(allowed))
+    }
+
+    // Removes the states of each layer which can't be combined with any state
+    // left in the other layer
+    fn restrict_layers(&self, cell: &mut PairState<A, B>) {
+        if self.compatible.is_empty() {
+            return;
+        }
+        let mut firsts = Vec::new();
+        cell.first.collect_final_states(&mut firsts);
+        let mut seconds = Vec::new();
+        cell.second.collect_final_states(&mut seconds);
+        for first in firsts {
+            if !self.is_compatible(&first, &cell.second) {
+                cell.first.clear_states(&first);
+            }
+        }
+        for second in seconds {
+            if !self.is_compatible(&cell.first, &second) {
+                cell.second.clear_states(&second);
+            }
+        }
+    }
+}
+
+// The state of one layer of each neighbor, in the order of that layer's rule
+fn layer_neighbors<A, B, T: Clone>(
+    neighbors: &[Option<PairState<A, B>>],
+    indices: &[usize],
+    layer: impl Fn(&PairState<A, B>) -> &T,
+) -> Vec<Option<T>> {
+    indices
+        .iter()
+        .map(|i| neighbors[*i].as_ref().map(|n| layer(n).clone()))
+        .collect()
+}
+
+impl<A, B, RuleA, RuleB, SpA, SpB, Sp> CollapseRule<PairState<A, B>, Sp>
+    for LayeredRule<A, B, RuleA, RuleB, SpA, SpB>
+where
+    A: SetState + State,
+    B: SetState + State,
+    SpA: Space<A>,
+    SpB: Space<B, Coordinate = SpA::Coordinate, CoordinateDelta = SpA::CoordinateDelta>,
+    Sp: Space<
+        PairState<A, B>,
+        Coordinate = SpA::Coordinate,
+        CoordinateDelta = SpA::CoordinateDelta,
+    >,
+    SpA::CoordinateDelta: PartialEq + Clone,
+    RuleA: CollapseRule<A, SpA>,
+    RuleB: CollapseRule<B, SpB>,
+{
+    fn neighbor_offsets(&self) -> Box<[Sp::CoordinateDelta]> {
+        self.neighbor_offsets.clone()
+    }
+
+    fn initialize(&self, coord: Sp::Coordinate, cell: &mut PairState<A, B>) {
+        self.first_rule.initialize(coord, &mut cell.first);
+        self.second_rule.initialize(coord, &mut cell.second);
+        self.restrict_layers(cell);
+    }
+
+    fn collapse(
+        &self,
+        coord: Sp::Coordinate,
+        cell: &mut PairState<A, B>,
+        neighbors: &[Option<PairState<A, B>>],
+    ) {
+        let first_neighbors = layer_neighbors(neighbors, &self.first_offsets, |n| &n.first);
+        self.first_rule
+            .collapse(coord, &mut cell.first, &first_neighbors);
+        let second_neighbors = layer_neighbors(neighbors, &self.second_offsets, |n| &n.second);
+        self.second_rule
+            .collapse(coord, &mut cell.second, &second_neighbors);
+        self.restrict_layers(cell);
+    }
+
+    fn observe(
+        &self,
+        coord: Sp::Coordinate,
+        cell: &mut PairState<A, B>,
+        neighbors: &[Option<PairState<A, B>>],
+        rng: &mut dyn RngCore,
+    ) {
+        // Final states which a layer's rule rejects are left out. This matters
+        // when a layer is itself layered, as not every combination of its
+        // layers' final states is compatible.
+        let first_neighbors = layer_neighbors(neighbors, &self.first_offsets, |n| &n.first);
+        let mut firsts = Vec::new();
+        cell.first.collect_final_states(&mut firsts);
+        firsts.retain(|first| {
+            let mut first = first.clone();
+            self.first_rule
+                .collapse(coord, &mut first, &first_neighbors);
+            !first.is_contradiction()
+        });
+        let second_neighbors = layer_neighbors(neighbors, &self.second_offsets, |n| &n.second);
+        let mut seconds = Vec::new();
+        cell.second.collect_final_states(&mut seconds);
+        seconds.retain(|second| {
+            let mut second = second.clone();
+            self.second_rule
+                .collapse(coord, &mut second, &second_neighbors);
+            !second.is_contradiction()
+        });
+        let mut pairs = Vec::new();
+        for first in firsts.iter() {
+            for second in seconds.iter() {
+                if self.is_compatible(first, second) {
+                    pairs.push((first, second));
+                }
+            }
+        }
+        if pairs.is_empty() {
+            // Leaves the cell without any states, so that the solver reports
+            // it rather than observing it again
+            let first = cell.first.clone();
+            cell.first.clear_states(&first);
+            return;
+        }
+
+        let index = choose_weighted(&pairs, rng, |(first, second)| (self.weight)(first, second));
+        let (first, second) = pairs[index];
+        cell.first = first.clone();
+        cell.second = second.clone();
+    }
+}
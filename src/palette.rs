@@ -0,0 +1,145 @@
+use bevy_utils::HashMap;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::hash::Hash;
+
+use crate::cube_grid::{grid_size, CubeGrid};
+use crate::Final;
+
+/// A compact copy of a collapsed [CubeGrid], which stores each distinct final
+/// value once in a palette, and each cell as an index into the palette. With
+/// the `serde` feature it serializes to a fraction of the size of the grid of
+/// states it was made from.
+///
+/// * `palette` - The distinct values, in the order they first appear
+/// * `indices` - The palette index of each cell
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PaletteGrid<T> {
+    pub width: isize,
+    pub length: isize,
+    pub height: isize,
+    pub palette: Vec<T>,
+    pub indices: Indices,
+}
+
+/// The palette indices of the cells in a [PaletteGrid]. Cells are in the same
+/// order as in a [CubeGrid], going along x first, then z, then y.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Indices {
+    /// One index per cell
+    Plain(Vec<u32>),
+    /// Runs of cells with the same index, as `(length, index)` pairs
+    RunLength(Vec<(u32, u32)>),
+}
+
+impl Indices {
+    /// The number of cells, or `None` if the runs add up to more cells than
+    /// fit in a `usize`
+    pub fn len(&self) -> Option<usize> {
+        match self {
+            Indices::Plain(indices) => Some(indices.len()),
+            Indices::RunLength(runs) => runs.iter().try_fold(0usize, |total, &(length, _)| {
+                total.checked_add(length as usize)
+            }),
+        }
+    }
+
+    /// Whether there are no cells
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+
+    /// The index of every cell. Check [Indices::len] first for indices from
+    /// untrusted data, as a few runs can stand for any number of cells.
+    pub fn to_plain(&self) -> Vec<u32> {
+        match self {
+            Indices::Plain(indices) => indices.clone(),
+            Indices::RunLength(runs) => runs
+                .iter()
+                .flat_map(|&(length, index)| std::iter::repeat_n(index, length as usize))
+                .collect(),
+        }
+    }
+
+    /// The indices of the cells grouped into runs
+    pub fn to_run_length(&self) -> Vec<(u32, u32)> {
+        match self {
+            Indices::Plain(indices) => {
+                let mut runs: Vec<(u32, u32)> = Vec::new();
+                for &index in indices {
+                    match runs.last_mut() {
+                        Some((length, last)) if *last == index => *length += 1,
+                        _ => runs.push((1, index)),
+                    }
+                }
+                runs
+            }
+            Indices::RunLength(runs) => runs.clone(),
+        }
+    }
+}
+
+impl<T: Eq + Hash + Clone> PaletteGrid<T> {
+    /// Creates a palette grid from the final values of a collapsed grid.
+    ///
+    /// Returns the coordinate of the first cell which isn't final as an error.
+    pub fn from_grid<S: Final<T> + 'static>(
+        grid: &CubeGrid<S>,
+    ) -> Result<Self, (isize, isize, isize)> {
+        let mut palette = Vec::new();
+        let mut lookup = HashMap::new();
+        let mut indices = Vec::new();
+        for y in 0..grid.height() {
+            for z in 0..grid.length() {
+                for x in 0..grid.width() {
+                    let value = grid[(x, y, z)].get().ok_or((x, y, z))?;
+                    let index = *lookup.entry(value.clone()).or_insert_with(|| {
+                        palette.push(value);
+                        palette.len() as u32 - 1
+                    });
+                    indices.push(index);
+                }
+            }
+        }
+        Ok(Self {
+            width: grid.width(),
+            length: grid.length(),
+            height: grid.height(),
+            palette,
+            indices: Indices::Plain(indices),
+        })
+    }
+
+    /// Stores the indices as runs, which is much smaller for grids with large
+    /// areas of the same value
+    pub fn run_length_encoded(mut self) -> Self {
+        self.indices = Indices::RunLength(self.indices.to_run_length());
+        self
+    }
+
+    /// Creates a grid holding the value of each cell. `to_state` turns the
+    /// values into cell states, e.g. `HashsetState::new_final`.
+    ///
+    /// Returns `None` if the number of indices doesn't match the dimensions,
+    /// or an index is outside the palette.
+    pub fn to_grid<S>(&self, to_state: impl Fn(&T) -> S) -> Option<CubeGrid<S>> {
+        // The size is checked before expanding any runs
+        let size = grid_size(self.width, self.length, self.height)?;
+        if self.indices.len() != Some(size) {
+            return None;
+        }
+        let indices = self.indices.to_plain();
+        if indices
+            .iter()
+            .any(|&index| index as usize >= self.palette.len())
+        {
+            return None;
+        }
+        let (width, length) = (self.width, self.length);
+        Some(CubeGrid::new(width, length, self.height, |x, y, z| {
+            to_state(&self.palette[indices[((y * length + z) * width + x) as usize] as usize])
+        }))
+    }
+}
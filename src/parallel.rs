@@ -0,0 +1,223 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use rayon::prelude::*;
+
+use crate::chunks::{chunk_seed, collapse_with_repairs, rule_reach};
+use crate::cube_grid::{CubeBox, CubeGrid};
+use crate::{CollapseRule, Contradiction, Region, Space, State};
+
+/// Perform the wave function collapse algorithm on a [CubeGrid], collapsing
+/// blocks of it on multiple threads.
+///
+/// The grid is split into blocks of `block_size` cells, which are colored like
+/// a 3D checkerboard. Blocks of the same color never touch, so they are
+/// collapsed concurrently, one color after another. Each block is constrained
+/// by the blocks around it which were already collapsed, and repairs
+/// contradictions by collapsing parts of itself again.
+///
+/// Every block draws from its own random generator, seeded from `seed` and
+/// the block's position, so the result doesn't depend on the number of
+/// threads.
+///
+/// Blocks are grown to at least the reach of the rule's neighbor offsets.
+///
+/// Blocks don't see each other across the faces of a wrapping grid, so the
+/// cells near those faces are collapsed again at the end, on a single thread.
+pub fn collapse_parallel<S, Rule>(
+    space: &mut CubeGrid<S>,
+    rule: &Rule,
+    block_size: (isize, isize, isize),
+    seed: u64,
+) -> Result<(), Contradiction<(isize, isize, isize)>>
+where
+    S: State + Send + Sync + 'static,
+    Rule: CollapseRule<S, CubeGrid<S>> + Sync,
+{
+    let reach = rule_reach(rule);
+    let block_size = (
+        block_size.0.max(reach).max(1),
+        block_size.1.max(reach).max(1),
+        block_size.2.max(reach).max(1),
+    );
+    let dimensions = (space.width(), space.height(), space.length());
+    let counts = (
+        (dimensions.0 + block_size.0 - 1) / block_size.0,
+        (dimensions.1 + block_size.1 - 1) / block_size.1,
+        (dimensions.2 + block_size.2 - 1) / block_size.2,
+    );
+
+    let block_box = |block: (isize, isize, isize)| {
+        let min = (
+            block.0 * block_size.0,
+            block.1 * block_size.1,
+            block.2 * block_size.2,
+        );
+        CubeBox {
+            min,
+            max: (
+                (min.0 + block_size.0).min(dimensions.0),
+                (min.1 + block_size.1).min(dimensions.1),
+                (min.2 + block_size.2).min(dimensions.2),
+            ),
+        }
+    };
+
+    let initial = space.clone();
+    let mut failed = Vec::new();
+    for color in 0..8 {
+        let mut blocks = Vec::new();
+        for by in 0..counts.1 {
+            for bz in 0..counts.2 {
+                for bx in 0..counts.0 {
+                    if (bx & 1) | (by & 1) << 1 | (bz & 1) << 2 == color {
+                        blocks.push((bx, by, bz));
+                    }
+                }
+            }
+        }
+
+        let grid = &*space;
+        let results: Vec<_> = blocks
+            .par_iter()
+            .map(|block| {
+                collapse_block(
+                    grid,
+                    rule,
+                    block_box(*block),
+                    reach,
+                    chunk_seed(seed, *block),
+                )
+            })
+            .collect();
+
+        // Failed blocks are left as they were, and reconciled with the blocks
+        // around them once every color is done
+        for (block, result) in blocks.into_iter().zip(results) {
+            match result {
+                Ok(collapsed) => write_block(space, collapsed),
+                Err(_) => failed.push(block),
+            }
+        }
+    }
+
+    // A block can be boxed in by its neighbors so that no repair inside it
+    // succeeds. Those blocks are collapsed again together with the halves of
+    // the blocks around them, on a single thread.
+    for block in failed {
+        let block = block_box(block);
+        let half = (block_size.0 / 2, block_size.1 / 2, block_size.2 / 2);
+        let region = CubeBox {
+            min: (
+                (block.min.0 - half.0).max(0),
+                (block.min.1 - half.1).max(0),
+                (block.min.2 - half.2).max(0),
+            ),
+            max: (
+                (block.max.0 + half.0).min(dimensions.0),
+                (block.max.1 + half.1).min(dimensions.1),
+                (block.max.2 + half.2).min(dimensions.2),
+            ),
+        };
+        for coord in initial.coordinate_list().iter() {
+            if region.contains(coord) {
+                space[*coord] = initial[*coord].clone();
+            }
+        }
+        let collapsed = collapse_block(space, rule, region, reach, chunk_seed(!seed, region.min))?;
+        write_block(space, collapsed);
+    }
+
+    // The seams of a wrapping grid are collapsed in place, so that the cells
+    // on the other side of each face are taken into account
+    let wrap = space.wrapped_axes();
+    if wrap.0 || wrap.1 || wrap.2 {
+        let half = (
+            (block_size.0 / 2).max(reach),
+            (block_size.1 / 2).max(reach),
+            (block_size.2 / 2).max(reach),
+        );
+        let near_face = |position: isize, half: isize, dimension: isize| {
+            position < half || position >= dimension - half
+        };
+        let seams = |coord: &(isize, isize, isize)| {
+            (wrap.0 && near_face(coord.0, half.0, dimensions.0))
+                || (wrap.1 && near_face(coord.1, half.1, dimensions.1))
+                || (wrap.2 && near_face(coord.2, half.2, dimensions.2))
+        };
+        for coord in initial.coordinate_list().iter() {
+            if seams(coord) {
+                space[*coord] = initial[*coord].clone();
+            }
+        }
+        let mut rng = StdRng::seed_from_u64(chunk_seed(!seed, (-1, -1, -1)));
+        collapse_with_repairs(space, (0, 0, 0), rule, &seams, &mut rng, 4, 8)?;
+    }
+    Ok(())
+}
+
+// Copies the collapsed cells of a block back into the grid
+fn write_block<S: State + 'static>(space: &mut CubeGrid<S>, collapsed: BlockResult<S>) {
+    let (origin, interior, padded) = collapsed;
+    for coord in padded.coordinate_list().iter() {
+        if interior.contains(coord) {
+            let (x, y, z) = *coord;
+            space[(origin.0 + x, origin.1 + y, origin.2 + z)] = padded[*coord].clone();
+        }
+    }
+}
+
+type BlockResult<S> = ((isize, isize, isize), CubeBox, CubeGrid<S>);
+
+// Collapses a block of the grid inside a copy of it padded with the cells
+// around it. Returns the copy along with the grid coordinate of its origin and
+// the block's cells within it.
+fn collapse_block<S: State + 'static, Rule: CollapseRule<S, CubeGrid<S>>>(
+    grid: &CubeGrid<S>,
+    rule: &Rule,
+    block: CubeBox,
+    padding: isize,
+    seed: u64,
+) -> Result<BlockResult<S>, Contradiction<(isize, isize, isize)>> {
+    // Padding stops at the edges of the grid, where the block has no neighbors
+    let origin = (
+        (block.min.0 - padding).max(0),
+        (block.min.1 - padding).max(0),
+        (block.min.2 - padding).max(0),
+    );
+    let end = (
+        (block.max.0 + padding).min(grid.width()),
+        (block.max.1 + padding).min(grid.height()),
+        (block.max.2 + padding).min(grid.length()),
+    );
+    let mut padded = CubeGrid::new(
+        end.0 - origin.0,
+        end.2 - origin.2,
+        end.1 - origin.1,
+        |x, y, z| grid[(origin.0 + x, origin.1 + y, origin.2 + z)].clone(),
+    );
+    let interior = CubeBox {
+        min: (
+            block.min.0 - origin.0,
+            block.min.1 - origin.1,
+            block.min.2 - origin.2,
+        ),
+        max: (
+            block.max.0 - origin.0,
+            block.max.1 - origin.1,
+            block.max.2 - origin.2,
+        ),
+    };
+
+    let mut rng = StdRng::seed_from_u64(seed);
+    let max_repairs = 8;
+    collapse_with_repairs(
+        &mut padded,
+        origin,
+        rule,
+        &interior,
+        &mut rng,
+        4,
+        max_repairs,
+    )?;
+    Ok((origin, interior, padded))
+}
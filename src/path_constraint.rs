@@ -0,0 +1,317 @@
+use bevy_utils::{HashMap, HashSet};
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::ops::RangeBounds;
+
+use crate::connectivity::{flood, only_has, separators};
+use crate::count_constraint::inclusive_bounds;
+use crate::{Contradiction, GlobalConstraint, SetState, Space, State};
+
+/// Global constraint which requires a walkable path between two cells, such as
+/// from the entrance of a level to its exit.
+///
+/// Both ends of the path are forced to be walkable, as is every cell which all
+/// remaining paths pass through, so no observation can cut the ends apart. The
+/// length of the path, in steps between neighbors, can also be limited:
+/// - The shortest path through cells which could be walkable must be no
+///   longer than the maximum
+/// - The shortest path through cells which are walkable must be no shorter
+///   than the minimum
+///
+/// With a maximum, every cell which all short enough paths pass through is
+/// forced to be walkable. With a minimum, cells which would join the ends too
+/// closely can't be walkable. That only looks one cell ahead, so a collapse
+/// can still be left with room for nothing but shorter paths, and fails more
+/// often the further the minimum is above the shortest possible path.
+pub struct PathConstraint<S, C, D> {
+    walkable: S,
+    deltas: Box<[D]>,
+    from: C,
+    to: C,
+    min_length: usize,
+    max_length: usize,
+    initialized: bool,
+    // Cells with at least one walkable state remaining
+    possible: HashSet<C>,
+    // Cells with only walkable states remaining
+    definite: HashSet<C>,
+    // Paths short enough which avoid a cell, for cells which might have to be
+    // walkable. They stay valid until one of their cells can't be walkable.
+    detours: HashMap<C, Vec<C>>,
+}
+
+impl<S: SetState + State, C, D: Clone> PathConstraint<S, C, D> {
+    /// * `walkable` - Every state which counts as walkable
+    /// * `deltas` - Offsets between cells which can be walked between
+    /// * `from`, `to` - The ends of the path
+    pub fn new(walkable: &S, deltas: &[D], from: C, to: C) -> Self {
+        Self {
+            walkable: walkable.clone(),
+            deltas: deltas.to_vec().into_boxed_slice(),
+            from,
+            to,
+            min_length: 0,
+            max_length: usize::MAX,
+            initialized: false,
+            possible: HashSet::default(),
+            definite: HashSet::default(),
+            detours: HashMap::default(),
+        }
+    }
+
+    /// Limits the length of the path, such as `10..=40` or `..20`
+    pub fn length(mut self, length: impl RangeBounds<usize>) -> Self {
+        (self.min_length, self.max_length) = inclusive_bounds(length);
+        self
+    }
+}
+
+impl<S: SetState + State, Sp: Space<S>> GlobalConstraint<S, Sp>
+    for PathConstraint<S, Sp::Coordinate, Sp::CoordinateDelta>
+{
+    fn propagate(
+        &mut self,
+        space: &mut Sp,
+        changed: &[Sp::Coordinate],
+        narrowed: &mut Vec<Sp::Coordinate>,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        // Everything is checked the first time
+        let first = !self.initialized;
+        if first {
+            self.initialized = true;
+            for end in [self.from, self.to] {
+                space[end].retain_states(&self.walkable);
+                narrowed.push(end);
+            }
+        }
+
+        // Paths can only get longer when cells can no longer be walkable, and
+        // shorter when cells have to be walkable
+        let mut removed = first;
+        let mut added = first;
+        for coord in changed {
+            let cell = &space[*coord];
+            if !cell.has_any_of(&self.walkable) {
+                removed |= self.possible.remove(coord);
+                self.definite.remove(coord);
+            } else {
+                self.possible.insert(*coord);
+                if only_has(cell, &self.walkable) {
+                    added |= self.definite.insert(*coord);
+                }
+            }
+        }
+
+        let too_long = removed
+            && shortest_path(space, self.from, self.to, &self.deltas, |coord| {
+                self.possible.contains(coord)
+            })
+            .is_none_or(|length| length > self.max_length);
+        let too_short = added
+            && shortest_path(space, self.from, self.to, &self.deltas, |coord| {
+                self.definite.contains(coord)
+            })
+            .is_some_and(|length| length < self.min_length);
+        if too_long || too_short {
+            return Err(Contradiction {
+                coordinate: self.to,
+            });
+        }
+
+        if added && self.min_length > 0 {
+            // Cells which would join the ends with a walkable path that is too
+            // short can't be walkable
+            let definite = |coord: &Sp::Coordinate| self.definite.contains(coord);
+            let from_distances = distances(space, self.from, &self.deltas, definite);
+            let to_distances = distances(space, self.to, &self.deltas, definite);
+            let mut neighbors = vec![None; self.deltas.len()];
+            let mut too_close = Vec::new();
+            for coord in self.possible.difference(&self.definite) {
+                space.neighbors(*coord, &self.deltas, &mut neighbors);
+                let closest = |distances: &HashMap<Sp::Coordinate, usize>| {
+                    neighbors
+                        .iter()
+                        .flatten()
+                        .filter_map(|neighbor| distances.get(neighbor))
+                        .min()
+                        .map(|distance| distance + 1)
+                };
+                if let (Some(from), Some(to)) = (closest(&from_distances), closest(&to_distances)) {
+                    if from + to < self.min_length {
+                        too_close.push(*coord);
+                    }
+                }
+            }
+            if !too_close.is_empty() {
+                // The rest is checked again once these cells are narrowed
+                too_close.sort();
+                for coord in too_close {
+                    space[coord].clear_states(&self.walkable);
+                    narrowed.push(coord);
+                }
+                return Ok(());
+            }
+        }
+
+        if !removed {
+            return Ok(());
+        }
+        let forced = if self.max_length == usize::MAX {
+            let reachable = flood(space, self.from, &self.deltas, |coord| {
+                self.possible.contains(coord)
+            });
+            separators(space, self.from, &self.deltas, &reachable, |coord| {
+                *coord == self.to
+            })
+        } else {
+            self.on_every_short_path(space)
+        };
+        for coord in forced {
+            if !self.definite.contains(&coord) {
+                space[coord].retain_states(&self.walkable);
+                narrowed.push(coord);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: SetState + State, C: Copy + Eq + Hash + Ord, D> PathConstraint<S, C, D> {
+    // Finds the cells which every path between the ends that is no longer
+    // than the maximum passes through. Such a cell lies on every shortest path
+    // as well, so only the cells which are alone at their distance among the
+    // shortest paths are checked, by looking for a path which avoids them.
+    fn on_every_short_path<Sp: Space<S, Coordinate = C, CoordinateDelta = D>>(
+        &mut self,
+        space: &Sp,
+    ) -> Vec<C> {
+        let possible = |coord: &C| self.possible.contains(coord);
+        let from_distances = distances(space, self.from, &self.deltas, possible);
+        let to_distances = distances(space, self.to, &self.deltas, possible);
+        let Some(&shortest) = from_distances.get(&self.to) else {
+            return Vec::new();
+        };
+        // The cells on shortest paths at each distance from the start
+        let mut on_shortest: HashMap<usize, Vec<C>> = HashMap::default();
+        for (coord, from) in from_distances.iter() {
+            if shortest
+                .checked_sub(*from)
+                .is_some_and(|to| to_distances.get(coord) == Some(&to))
+            {
+                on_shortest.entry(*from).or_default().push(*coord);
+            }
+        }
+
+        let mut forced = Vec::new();
+        let mut detours = Vec::new();
+        for cells in on_shortest.values() {
+            let [coord] = cells[..] else {
+                continue;
+            };
+            if self.definite.contains(&coord) {
+                continue;
+            }
+            let known = self
+                .detours
+                .get(&coord)
+                .is_some_and(|detour| detour.iter().all(possible));
+            if known {
+                continue;
+            }
+            let detour = path_between(space, self.from, self.to, &self.deltas, |other| {
+                *other != coord && possible(other)
+            });
+            match detour {
+                Some(detour) if detour.len() - 1 <= self.max_length => {
+                    detours.push((coord, detour));
+                }
+                _ => forced.push(coord),
+            }
+        }
+        self.detours.extend(detours);
+        forced.sort();
+        forced
+    }
+}
+
+// Finds a shortest path between `from` and `to` through cells accepted by
+// `include`, including both ends
+fn path_between<St, Sp: Space<St>>(
+    space: &Sp,
+    from: Sp::Coordinate,
+    to: Sp::Coordinate,
+    deltas: &[Sp::CoordinateDelta],
+    include: impl Fn(&Sp::Coordinate) -> bool,
+) -> Option<Vec<Sp::Coordinate>> {
+    if !include(&from) || !include(&to) {
+        return None;
+    }
+    let mut previous = HashMap::default();
+    let mut to_visit = VecDeque::new();
+    let mut neighbors = vec![None; deltas.len()];
+    previous.insert(from, from);
+    to_visit.push_back(from);
+    while let Some(visiting) = to_visit.pop_front() {
+        if visiting == to {
+            let mut path = vec![to];
+            let mut coord = to;
+            while coord != from {
+                coord = previous[&coord];
+                path.push(coord);
+            }
+            return Some(path);
+        }
+        space.neighbors(visiting, deltas, &mut neighbors);
+        for neighbor in neighbors.iter().flatten() {
+            if include(neighbor) && !previous.contains_key(neighbor) {
+                previous.insert(*neighbor, visiting);
+                to_visit.push_back(*neighbor);
+            }
+        }
+    }
+    None
+}
+
+// Gets the number of steps along the shortest path between `from` and `to`
+// through cells accepted by `include`
+fn shortest_path<St, Sp: Space<St>>(
+    space: &Sp,
+    from: Sp::Coordinate,
+    to: Sp::Coordinate,
+    deltas: &[Sp::CoordinateDelta],
+    include: impl Fn(&Sp::Coordinate) -> bool,
+) -> Option<usize> {
+    if !include(&to) {
+        return None;
+    }
+    distances(space, from, deltas, include).get(&to).copied()
+}
+
+// Gets the number of steps from `start` to every cell reachable from it
+// through cells accepted by `include`
+fn distances<St, Sp: Space<St>>(
+    space: &Sp,
+    start: Sp::Coordinate,
+    deltas: &[Sp::CoordinateDelta],
+    include: impl Fn(&Sp::Coordinate) -> bool,
+) -> HashMap<Sp::Coordinate, usize> {
+    let mut distances = HashMap::default();
+    if !include(&start) {
+        return distances;
+    }
+    let mut to_visit = VecDeque::new();
+    let mut neighbors = vec![None; deltas.len()];
+    distances.insert(start, 0);
+    to_visit.push_back(start);
+    while let Some(visiting) = to_visit.pop_front() {
+        let distance = distances[&visiting];
+        space.neighbors(visiting, deltas, &mut neighbors);
+        for neighbor in neighbors.iter().flatten() {
+            if include(neighbor) && !distances.contains_key(neighbor) {
+                distances.insert(*neighbor, distance + 1);
+                to_visit.push_back(*neighbor);
+            }
+        }
+    }
+    distances
+}
@@ -0,0 +1,118 @@
+use bevy_utils::HashMap;
+use std::collections::BTreeSet;
+use std::hash::Hash;
+
+use crate::SetState;
+
+/// Why a cell was narrowed, as recorded by a [crate::Solver] with provenance
+/// recording turned on
+#[derive(Clone, PartialEq, Debug)]
+pub enum Cause<C, S> {
+    /// The cell was observed
+    Observed,
+    /// The collapse rule narrowed the cell because of a single neighbor
+    ///
+    /// * `offset_index` - The index of the neighbor in the rule's
+    ///   neighbor offsets
+    /// * `neighbor_state` - The neighbor's state at the time
+    /// * `removed` - Each final state the rule removed with only this neighbor
+    ///   present. The rule's entry for each of them at `offset_index`, such as
+    ///   its `valid_neighbors` list in that direction, allows none of
+    ///   `neighbor_state`.
+    Neighbor {
+        neighbor: C,
+        offset_index: usize,
+        neighbor_state: S,
+        removed: Vec<S>,
+    },
+    /// The collapse rule narrowed the cell because of several neighbors
+    /// together, without any one of them narrowing it on its own
+    Neighbors,
+    /// A global constraint narrowed the cell
+    Constraint,
+    /// The cell was pinned or restricted through the solver
+    Restricted,
+}
+
+/// A single narrowing of a cell
+///
+/// * `coordinate` - The cell which was narrowed
+/// * `state` - The cell's state after being narrowed
+/// * `causes` - Why the cell was narrowed. When the collapse rule narrowed
+///   it, every neighbor which narrows it on its own is listed.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Provenance<C, S> {
+    pub coordinate: C,
+    pub state: S,
+    pub causes: Vec<Cause<C, S>>,
+}
+
+// Every narrowing recorded by a solver, in the order they happened
+pub(crate) struct ProvenanceLog<C, S> {
+    events: Vec<Provenance<C, S>>,
+    by_cell: HashMap<C, Vec<usize>>,
+    // Lists the final states in a state which are no longer in another one
+    removed: fn(&S, &S) -> Vec<S>,
+}
+
+impl<C: Copy + Eq + Hash, S: Clone> ProvenanceLog<C, S> {
+    pub(crate) fn new() -> Self
+    where
+        S: SetState,
+    {
+        Self {
+            events: Vec::new(),
+            by_cell: HashMap::default(),
+            removed: |before, after| {
+                let mut removed = before.clone();
+                removed.clear_states(after);
+                let mut states = Vec::new();
+                removed.collect_final_states(&mut states);
+                states
+            },
+        }
+    }
+
+    // The final states in `before` which are no longer in `after`
+    pub(crate) fn removed(&self, before: &S, after: &S) -> Vec<S> {
+        (self.removed)(before, after)
+    }
+
+    pub(crate) fn record(&mut self, coordinate: C, state: &S, causes: Vec<Cause<C, S>>) {
+        self.by_cell
+            .entry(coordinate)
+            .or_default()
+            .push(self.events.len());
+        self.events.push(Provenance {
+            coordinate,
+            state: state.clone(),
+            causes,
+        });
+    }
+
+    // Every narrowing of `coord`, along with the narrowings of neighbors
+    // which led to them, back to the observations which started them
+    pub(crate) fn explain(&self, coord: C) -> Vec<Provenance<C, S>> {
+        let mut found = BTreeSet::new();
+        let mut to_visit: Vec<(C, usize)> = vec![(coord, self.events.len())];
+        while let Some((cell, before)) = to_visit.pop() {
+            let Some(indices) = self.by_cell.get(&cell) else {
+                continue;
+            };
+            for index in indices.iter().take_while(|index| **index < before) {
+                if !found.insert(*index) {
+                    continue;
+                }
+                for cause in self.events[*index].causes.iter() {
+                    if let Cause::Neighbor { neighbor, .. } = cause {
+                        to_visit.push((*neighbor, *index));
+                    }
+                }
+            }
+        }
+        found
+            .into_iter()
+            .map(|index| self.events[index].clone())
+            .collect()
+    }
+}
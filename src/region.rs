@@ -0,0 +1,69 @@
+use bevy_utils::HashSet;
+use std::hash::Hash;
+
+use crate::{CollapseRule, Contradiction, Solver, Space, State};
+
+/// A set of coordinates within a space
+pub trait Region<C> {
+    fn contains(&self, coord: &C) -> bool;
+}
+
+impl<C, F: Fn(&C) -> bool> Region<C> for F {
+    fn contains(&self, coord: &C) -> bool {
+        self(coord)
+    }
+}
+
+impl<C: PartialEq> Region<C> for [C] {
+    fn contains(&self, coord: &C) -> bool {
+        self.iter().any(|c| c == coord)
+    }
+}
+
+impl<C: PartialEq> Region<C> for Vec<C> {
+    fn contains(&self, coord: &C) -> bool {
+        self.as_slice().contains(coord)
+    }
+}
+
+impl<C: Eq + Hash> Region<C> for HashSet<C> {
+    fn contains(&self, coord: &C) -> bool {
+        HashSet::contains(self, coord)
+    }
+}
+
+/// Collapse a region of an already collapsed space again.
+///
+/// Every cell in `region` is reset to `open_state` and collapsed anew, using
+/// the cells around the region as a fixed boundary. Cells outside the region
+/// are never modified.
+///
+/// If the boundary leaves the region unsolvable, the region is restored to
+/// what it was before and the contradiction is returned.
+pub fn regenerate_region<Rule: CollapseRule<St, Sp>, St: State, Sp: Space<St>>(
+    space: &mut Sp,
+    rule: &Rule,
+    region: &(impl Region<Sp::Coordinate> + ?Sized),
+    open_state: &St,
+) -> Result<(), Contradiction<Sp::Coordinate>> {
+    let previous: Vec<(Sp::Coordinate, St)> = space
+        .coordinate_list()
+        .iter()
+        .filter(|coord| region.contains(coord))
+        .map(|coord| (*coord, space[*coord].clone()))
+        .collect();
+    for (coord, _) in previous.iter() {
+        space[*coord] = open_state.clone();
+    }
+
+    let result = Solver::new(&mut *space, rule).and_then(|mut solver| {
+        solver.confine(region);
+        solver.run()
+    });
+    if result.is_err() {
+        for (coord, state) in previous {
+            space[coord] = state;
+        }
+    }
+    result
+}
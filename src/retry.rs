@@ -0,0 +1,79 @@
+use bevy_utils::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{CollapseRule, Contradiction, Solver, Space, State};
+
+/// Statistics gathered by [collapse_with_retries]
+///
+/// * `attempts` - The number of times the space was collapsed
+/// * `seeds` - The seed of each attempt. Collapsing with a [StdRng] seeded
+///   with it makes the same choices again.
+/// * `contradictions` - The contradiction which ended each failed attempt
+/// * `observations` - The number of cells observed during each attempt
+/// * `duration` - Total time spent across all attempts
+#[derive(Clone, Debug)]
+pub struct RetryStats<C> {
+    pub attempts: usize,
+    pub seeds: Vec<u64>,
+    pub contradictions: Vec<Contradiction<C>>,
+    pub observations: Vec<usize>,
+    pub duration: Duration,
+}
+
+/// The collapsed space and statistics of a successful [collapse_with_retries],
+/// or just the statistics if every attempt failed
+pub type RetryResult<Sp, C> = Result<(Sp, RetryStats<C>), RetryStats<C>>;
+
+/// Perform the wave function collapse algorithm, starting over with a new
+/// space whenever a contradiction is hit.
+///
+/// * `space_factory` - Creates the initial space for each attempt
+/// * `rule` - The collapse rule to use
+/// * `max_attempts` - The number of attempts to make before giving up
+/// * `rng` - Source of randomness. Each attempt draws a new seed from it, so
+///   each one makes different choices and can be repeated on its own.
+///
+/// Returns the collapsed space of the first successful attempt, or `Err` with
+/// the statistics of every attempt if none of them succeeded.
+pub fn collapse_with_retries<Rule: CollapseRule<St, Sp>, St: State, Sp: Space<St>, R: Rng>(
+    mut space_factory: impl FnMut() -> Sp,
+    rule: &Rule,
+    max_attempts: usize,
+    rng: &mut R,
+) -> RetryResult<Sp, Sp::Coordinate> {
+    let start = Instant::now();
+    let mut stats = RetryStats {
+        attempts: 0,
+        seeds: Vec::new(),
+        contradictions: Vec::new(),
+        observations: Vec::new(),
+        duration: Duration::ZERO,
+    };
+    while stats.attempts < max_attempts {
+        stats.attempts += 1;
+        let seed = rng.gen();
+        stats.seeds.push(seed);
+        let mut space = space_factory();
+        let result = match Solver::with_rng(&mut space, rule, StdRng::seed_from_u64(seed)) {
+            Ok(mut solver) => {
+                let result = solver.run();
+                stats.observations.push(solver.observations());
+                result
+            }
+            Err(contradiction) => {
+                stats.observations.push(0);
+                Err(contradiction)
+            }
+        };
+        match result {
+            Ok(()) => {
+                stats.duration = start.elapsed();
+                return Ok((space, stats));
+            }
+            Err(contradiction) => stats.contradictions.push(contradiction),
+        }
+    }
+    stats.duration = start.elapsed();
+    Err(stats)
+}
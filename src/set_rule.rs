@@ -1,64 +1,189 @@
-use crate::{CollapseRule, Final, InvertDelta, SetState, Space, State};
+use crate::{CollapseRule, Final, InvertDelta, Region, SetState, Space, State};
 use bevy_utils::HashMap;
-use rand::{thread_rng, Rng};
+use rand::{Rng, RngCore};
 use std::hash::Hash;
+use std::marker::PhantomData;
 
-pub trait SetCollapseObserver<S: State> {
-    fn observe(&self, cell: &mut S, neighbors: &[Option<S>]);
+/// Chooses the final state of a cell when the collapse rule observes it.
+///
+/// * `C` - The coordinate type of the space being collapsed
+pub trait SetCollapseObserver<S: State, C> {
+    /// Forces `cell`, located at `coord`, into one of its final states, using
+    /// `rng` for any random choices.
+    fn observe(&self, coord: C, cell: &mut S, neighbors: &[Option<S>], rng: &mut dyn RngCore);
 }
 
 #[derive(Clone)]
 pub struct UniformSetCollapseObserver;
 
-impl<S: SetState + State + Clone> SetCollapseObserver<S> for UniformSetCollapseObserver {
-    fn observe(&self, cell: &mut S, _: &[Option<S>]) {
+impl<S: SetState + State + Clone, C> SetCollapseObserver<S, C> for UniformSetCollapseObserver {
+    fn observe(&self, _: C, cell: &mut S, _: &[Option<S>], rng: &mut dyn RngCore) {
         let mut final_states = Vec::new();
         cell.collect_final_states(&mut final_states);
-        *cell = final_states[thread_rng().gen_range(0..final_states.len())].clone();
+        *cell = final_states[rng.gen_range(0..final_states.len())].clone();
     }
 }
 
+// States are chosen based on their relative weight compared to the sum of weights of remaining states.
+// States with a weight of 0 will never be chosen by the observer (they can still be picked by the
+// algorithm if that's the only possible remaining state, and are picked uniformly if every
+// remaining state has a weight of 0)
+fn observe_weighted<S: SetState + Clone>(
+    cell: &mut S,
+    rng: &mut dyn RngCore,
+    weight: impl Fn(&S) -> u32,
+) {
+    let mut final_states = Vec::new();
+    cell.collect_final_states(&mut final_states);
+    if !final_states.is_empty() {
+        *cell = final_states[choose_weighted(&final_states, rng, weight)].clone();
+    }
+}
+
+// Picks the index of one of `choices` as described for observe_weighted above
+pub(crate) fn choose_weighted<T>(
+    choices: &[T],
+    rng: &mut dyn RngCore,
+    weight: impl Fn(&T) -> u32,
+) -> usize {
+    // calculate running sum of all weights for each state
+    let mut weight_vec: Vec<u32> = Vec::with_capacity(choices.len());
+    let mut total = 0;
+    for choice in choices.iter() {
+        total += weight(choice);
+        weight_vec.push(total);
+    }
+
+    if total == 0 {
+        return rng.gen_range(0..choices.len());
+    }
+    let rand = rng.gen_range(0..total);
+    weight_vec
+        .into_iter()
+        .position(|weight| weight > rand)
+        .unwrap()
+}
+
 #[derive(Clone)]
 pub struct WeightedSetCollapseObserver<T: Eq + Hash + Clone> {
     pub weights: HashMap<T, u32>,
 }
 
-// States are chosen based on their relative weight compared to the sum of weights of remaining states.
-// States with a weight of 0 will never be chosen by the observer (they can still be picked by the
-// algorithm if that's the only possible remaining state)
-impl<S: SetState + State + Clone + Final<T>, T: Eq + Hash + Clone> SetCollapseObserver<S>
+impl<S: SetState + State + Clone + Final<T>, T: Eq + Hash + Clone, C> SetCollapseObserver<S, C>
     for WeightedSetCollapseObserver<T>
 {
-    fn observe(&self, cell: &mut S, _: &[Option<S>]) {
-        let mut final_states = Vec::new();
-        cell.collect_final_states(&mut final_states);
+    fn observe(&self, _: C, cell: &mut S, _: &[Option<S>], rng: &mut dyn RngCore) {
+        observe_weighted(cell, rng, |state| {
+            *self.weights.get(&state.get().unwrap()).unwrap()
+        });
+    }
+}
 
-        // calculate running sum of all weights for each state
-        let mut weight_vec: Vec<u32> = vec![0; final_states.len()];
-        if !final_states.is_empty() {
-            let state = final_states[0].get().unwrap();
-            weight_vec[0] = *self.weights.get(&state).unwrap();
-        }
-        for (i, final_state) in final_states.iter().enumerate().skip(1) {
-            let state = final_state.get().unwrap();
-            weight_vec[i] = *self.weights.get(&state).unwrap() + weight_vec[i - 1];
+/// Weighted observer where the weight of a state depends on the position of
+/// the cell being observed, such as favoring some states near the ground or
+/// driving density from a noise field.
+///
+/// * `weight_fn` - Gives the weight of a state at a coordinate, weighted as
+///   in [WeightedSetCollapseObserver]
+#[derive(Clone)]
+pub struct PositionalWeightedObserver<T, F> {
+    weight_fn: F,
+    marker: PhantomData<fn(&T)>,
+}
+
+impl<T, F> PositionalWeightedObserver<T, F> {
+    pub fn new(weight_fn: F) -> Self {
+        Self {
+            weight_fn,
+            marker: PhantomData,
         }
+    }
+}
 
-        let rand = thread_rng().gen_range(0..*weight_vec.last().unwrap());
-        let mut prev = 0;
-        for (i, weight) in weight_vec.into_iter().enumerate() {
-            if weight >= rand && weight - prev != 0 {
-                *cell = final_states[i].clone();
-                return;
-            }
-            prev = weight;
+impl<S, T, C, F> SetCollapseObserver<S, C> for PositionalWeightedObserver<T, F>
+where
+    S: SetState + State + Clone + Final<T>,
+    T: Eq + Hash + Clone,
+    F: Fn(&C, &T) -> u32,
+{
+    fn observe(&self, coord: C, cell: &mut S, _: &[Option<S>], rng: &mut dyn RngCore) {
+        observe_weighted(cell, rng, |state| {
+            (self.weight_fn)(&coord, &state.get().unwrap())
+        });
+    }
+}
+
+/// Observer which scores each candidate state by the already resolved
+/// neighbors of the cell, such as continuing a road in the same direction or
+/// avoiding placing a decoration next to itself.
+///
+/// * `score_fn` - Scores a candidate state given the final states of the
+///   neighbors, in the order of [crate::CollapseRule::neighbor_offsets]
+///   (`None` for neighbors which are missing or unresolved). Scores are used
+///   as weights, as in [WeightedSetCollapseObserver]
+#[derive(Clone)]
+pub struct ContextualObserver<T, F> {
+    score_fn: F,
+    marker: PhantomData<fn(&T)>,
+}
+
+impl<T, F: Fn(&T, &[Option<T>]) -> u32> ContextualObserver<T, F> {
+    pub fn new(score_fn: F) -> Self {
+        Self {
+            score_fn,
+            marker: PhantomData,
         }
     }
 }
 
-pub struct SetCollapseRule<S: SetState + State + Sized, Sp: Space<S>, O: SetCollapseObserver<S>> {
+/// Score function for [ContextualObserver] which makes states less likely to
+/// be chosen next to resolved neighbors in the same state. Each identical
+/// neighbor divides the chance of a state being picked by `penalty`.
+pub fn penalize_identical_neighbors<T: Eq>(
+    penalty: u32,
+) -> impl Fn(&T, &[Option<T>]) -> u32 + Clone {
+    move |state, neighbors| {
+        let identical = neighbors
+            .iter()
+            .filter(|neighbor| neighbor.as_ref() == Some(state))
+            .count() as u32;
+        penalty
+            .checked_pow(identical)
+            .map_or(0, |divisor| (1 << 16) / divisor.max(1))
+    }
+}
+
+impl<S, T, C, F> SetCollapseObserver<S, C> for ContextualObserver<T, F>
+where
+    S: SetState + State + Clone + Final<T>,
+    T: Eq + Hash + Clone,
+    F: Fn(&T, &[Option<T>]) -> u32,
+{
+    fn observe(&self, _: C, cell: &mut S, neighbors: &[Option<S>], rng: &mut dyn RngCore) {
+        let resolved: Vec<Option<T>> = neighbors
+            .iter()
+            .map(|neighbor| neighbor.as_ref().and_then(|state| state.get()))
+            .collect();
+        observe_weighted(cell, rng, |state| {
+            (self.score_fn)(&state.get().unwrap(), &resolved[..])
+        });
+    }
+}
+
+// Each final state paired with the states allowed at each neighbor offset
+type StateRules<S> = Box<[(S, Box<[Option<S>]>)]>;
+
+// States paired with the only region they may appear in
+type Restrictions<S, C> = Vec<(S, Box<dyn Region<C> + Send + Sync>)>;
+
+pub struct SetCollapseRule<
+    S: SetState + State + Sized,
+    Sp: Space<S>,
+    O: SetCollapseObserver<S, Sp::Coordinate>,
+> {
     neighbor_offsets: Box<[Sp::CoordinateDelta]>,
-    state_rules: Box<[(S, Box<[Option<S>]>)]>,
+    state_rules: StateRules<S>,
+    restrictions: Restrictions<S, Sp::Coordinate>,
     observer: O,
 }
 
@@ -84,16 +209,20 @@ impl<S: SetState + Clone> StateRule<S> {
 pub struct SetCollapseRuleBuilder<
     S: SetState + State,
     Sp: Space<S>,
-    O: SetCollapseObserver<S> + Clone,
+    O: SetCollapseObserver<S, Sp::Coordinate> + Clone,
 > {
     neighbor_offsets: Vec<Sp::CoordinateDelta>,
     state_rules: Vec<StateRule<S>>,
     observer: O,
     all_state: S,
+    restrictions: Restrictions<S, Sp::Coordinate>,
 }
 
-impl<S: SetState + State + PartialEq, Sp: Space<S>, O: SetCollapseObserver<S> + Clone>
-    SetCollapseRuleBuilder<S, Sp, O>
+impl<
+        S: SetState + State + PartialEq,
+        Sp: Space<S>,
+        O: SetCollapseObserver<S, Sp::Coordinate> + Clone,
+    > SetCollapseRuleBuilder<S, Sp, O>
 where
     Sp::CoordinateDelta: Eq + Clone + InvertDelta,
 {
@@ -103,9 +232,22 @@ where
             state_rules: Vec::new(),
             observer,
             all_state,
+            restrictions: Vec::new(),
         }
     }
 
+    // Only allow the states in `state` inside `region`, e.g. within a band of
+    // heights. Cells outside of it lose those states before the collapse
+    // starts.
+    pub fn restrict_to_region(
+        mut self,
+        state: &S,
+        region: impl Region<Sp::Coordinate> + Send + Sync + 'static,
+    ) -> Self {
+        self.restrictions.push((state.clone(), Box::new(region)));
+        self
+    }
+
     // Set the allowed neighbors for a cell based on their coordinate deltas
     //
     // Rules aren't added symmetrically - only provided rules will be added
@@ -177,14 +319,15 @@ where
         SetCollapseRule {
             neighbor_offsets: self.neighbor_offsets.into_boxed_slice(),
             state_rules: state_rules.into_boxed_slice(),
+            restrictions: self.restrictions,
             observer: self.observer,
         }
     }
 }
 
 // A collapse rule implementation that works with implementors of [crate::SetState<T>]
-impl<S: SetState + State, Sp: Space<S>, O: SetCollapseObserver<S>> CollapseRule<S, Sp>
-    for SetCollapseRule<S, Sp, O>
+impl<S: SetState + State, Sp: Space<S>, O: SetCollapseObserver<S, Sp::Coordinate>>
+    CollapseRule<S, Sp> for SetCollapseRule<S, Sp, O>
 where
     Sp::CoordinateDelta: Clone,
 {
@@ -192,7 +335,15 @@ where
         self.neighbor_offsets.clone()
     }
 
-    fn collapse(&self, cell: &mut S, neighbors: &[Option<S>]) {
+    fn initialize(&self, coord: Sp::Coordinate, cell: &mut S) {
+        for (state, region) in self.restrictions.iter() {
+            if !region.contains(&coord) {
+                cell.clear_states(state);
+            }
+        }
+    }
+
+    fn collapse(&self, _: Sp::Coordinate, cell: &mut S, neighbors: &[Option<S>]) {
         for (state, allowed_neighbors) in &self.state_rules[..] {
             if cell.has_any_of(state) {
                 for i in 0..neighbors.len() {
@@ -211,7 +362,13 @@ where
         }
     }
 
-    fn observe(&self, cell: &mut S, neighbors: &[Option<S>]) {
-        self.observer.observe(cell, neighbors);
+    fn observe(
+        &self,
+        coord: Sp::Coordinate,
+        cell: &mut S,
+        neighbors: &[Option<S>],
+        rng: &mut dyn RngCore,
+    ) {
+        self.observer.observe(coord, cell, neighbors, rng);
     }
 }
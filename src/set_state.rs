@@ -8,6 +8,15 @@ pub trait SetState: Sized {
     fn has_any_of(&self, states: &Self) -> bool;
     /// Removes states from `self` that are present in `states`
     fn clear_states(&mut self, states: &Self);
+    /// Removes states from `self` that are not present in `states`
+    fn retain_states(&mut self, states: &Self)
+    where
+        Self: Clone,
+    {
+        let mut removed = self.clone();
+        removed.clear_states(states);
+        self.clear_states(&removed);
+    }
     /// Separates out all the final (0-entropy) states from this state into a Vec
     fn collect_final_states(&self, states: &mut Vec<Self>);
 }
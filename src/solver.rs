@@ -0,0 +1,739 @@
+use bevy_utils::HashSet;
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+
+use rand::rngs::ThreadRng;
+use rand::{thread_rng, Rng};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::provenance::ProvenanceLog;
+use crate::{
+    Budget, Cause, CollapseHooks, CollapseRule, GlobalConstraint, Provenance, Region, SetState,
+    Space, State, Status,
+};
+
+/// Error produced when a cell is left without any possible states, meaning
+/// the space can't be collapsed any further.
+///
+/// * `coordinate` - The cell which has no remaining states
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Contradiction<C> {
+    pub coordinate: C,
+}
+
+impl<C: fmt::Debug> fmt::Display for Contradiction<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no possible states remain for cell {:?}",
+            self.coordinate
+        )
+    }
+}
+
+impl<C: fmt::Debug> std::error::Error for Contradiction<C> {}
+
+/// The progress of a [Solver], saved with [Solver::save]. With the `serde`
+/// feature, this can be serialized as long as the space and random number
+/// generator can be. [crate::ChaCha8Rng] is a generator which can.
+///
+/// * `space` - The contents of the space being collapsed
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct SolverState<Sp, C, R> {
+    pub space: Sp,
+    unresolved: Vec<C>,
+    to_propagate: Vec<C>,
+    changed: Vec<C>,
+    rng: R,
+    observations: usize,
+    propagation_steps: usize,
+    confined: Option<Vec<C>>,
+    // Whether the rule has yet to initialize the cells
+    #[cfg_attr(feature = "serde", serde(default))]
+    uninitialized: bool,
+}
+
+impl<Sp, C, R> SolverState<Sp, C, R> {
+    // Separates the saved space from the rest of the state
+    fn take_space(self) -> (Sp, SolverState<(), C, R>) {
+        let state = SolverState {
+            space: (),
+            unresolved: self.unresolved,
+            to_propagate: self.to_propagate,
+            changed: self.changed,
+            rng: self.rng,
+            observations: self.observations,
+            propagation_steps: self.propagation_steps,
+            confined: self.confined,
+            uninitialized: self.uninitialized,
+        };
+        (self.space, state)
+    }
+}
+
+/// Step-by-step driver for the wave function collapse algorithm.
+///
+/// [crate::collapse] runs a solver to completion in a single call. Driving one
+/// directly allows running the algorithm one observation at a time, and
+/// constraining cells before the run or in between steps.
+///
+/// * `R` - The source of randomness for choosing cells and states
+pub struct Solver<'a, St: State, Sp: Space<St>, Rule: CollapseRule<St, Sp>, R: Rng = ThreadRng> {
+    space: &'a mut Sp,
+    rule: &'a Rule,
+    rng: R,
+    observations: usize,
+    propagation_steps: usize,
+    // Absolute limits for the run in progress, if it has a budget
+    limits: Option<Budget>,
+    hooks: Option<Box<dyn CollapseHooks<St, Sp> + 'a>>,
+    total_cells: usize,
+    neighbor_directions: Box<[Sp::CoordinateDelta]>,
+    unresolved_set: BTreeSet<Sp::Coordinate>,
+    lowest_entropy_set: Vec<Sp::Coordinate>,
+    to_propagate: VecDeque<Sp::Coordinate>,
+    neighbors: Box<[Option<Sp::Coordinate>]>,
+    neighbor_states: Box<[Option<St>]>,
+    constraints: Vec<Box<dyn GlobalConstraint<St, Sp> + 'a>>,
+    changed: Vec<Sp::Coordinate>,
+    narrowed: Vec<Sp::Coordinate>,
+    // Cells the solver is limited to modifying, if it has been confined
+    confined: Option<HashSet<Sp::Coordinate>>,
+    // Cells are initialized when the solver first propagates, so that
+    // confining it beforehand keeps the rule from initializing other cells
+    uninitialized: bool,
+    provenance: Option<ProvenanceLog<Sp::Coordinate, St>>,
+}
+
+impl<'a, St: State, Sp: Space<St>, Rule: CollapseRule<St, Sp>> Solver<'a, St, Sp, Rule> {
+    /// Creates a solver for `space`, which is modified in-place as the solver
+    /// runs. Fails if any cell of the space starts out with no possible states.
+    pub fn new(space: &'a mut Sp, rule: &'a Rule) -> Result<Self, Contradiction<Sp::Coordinate>> {
+        Self::with_rng(space, rule, thread_rng())
+    }
+}
+
+impl<'a, St: State, Sp: Space<St>, Rule: CollapseRule<St, Sp>, R: Rng> Solver<'a, St, Sp, Rule, R> {
+    /// Creates a solver as in [Solver::new], which makes its random choices
+    /// with `rng`.
+    pub fn with_rng(
+        space: &'a mut Sp,
+        rule: &'a Rule,
+        rng: R,
+    ) -> Result<Self, Contradiction<Sp::Coordinate>> {
+        let mut unresolved_set = BTreeSet::new();
+        let mut to_propagate = VecDeque::new();
+        let coordinates = space.coordinate_list();
+        for coord in &coordinates[..] {
+            let cell = &space[*coord];
+            if cell.is_contradiction() {
+                return Err(Contradiction { coordinate: *coord });
+            }
+            if cell.entropy() > 0 {
+                unresolved_set.insert(*coord);
+                to_propagate.push_back(*coord);
+            }
+        }
+        Ok(Self::from_parts(
+            space,
+            rule,
+            SolverState {
+                space: (),
+                unresolved: unresolved_set.into_iter().collect(),
+                to_propagate: to_propagate.into(),
+                changed: Vec::new(),
+                rng,
+                observations: 0,
+                propagation_steps: 0,
+                confined: None,
+                uninitialized: true,
+            },
+        ))
+    }
+
+    /// Recreates a solver from a state saved by [Solver::save], replacing the
+    /// contents of `space` with the saved ones. Running the resumed solver
+    /// gives the same result as the solver which was saved would have.
+    ///
+    /// Global constraints and hooks aren't part of the saved state, and need
+    /// to be added to the resumed solver again.
+    pub fn resume(
+        space: &'a mut Sp,
+        rule: &'a Rule,
+        state: SolverState<Sp, Sp::Coordinate, R>,
+    ) -> Self {
+        let (saved_space, state) = state.take_space();
+        *space = saved_space;
+        Self::from_parts(space, rule, state)
+    }
+
+    fn from_parts(
+        space: &'a mut Sp,
+        rule: &'a Rule,
+        state: SolverState<(), Sp::Coordinate, R>,
+    ) -> Self {
+        let neighbor_directions = rule.neighbor_offsets();
+        let neighbors = vec![None; neighbor_directions.len()].into_boxed_slice();
+        let neighbor_states = vec![None; neighbor_directions.len()].into_boxed_slice();
+        let confined: Option<HashSet<Sp::Coordinate>> =
+            state.confined.map(|cells| cells.into_iter().collect());
+        Self {
+            total_cells: confined
+                .as_ref()
+                .map_or_else(|| space.coordinate_list().len(), |cells| cells.len()),
+            space,
+            rule,
+            rng: state.rng,
+            observations: state.observations,
+            propagation_steps: state.propagation_steps,
+            limits: None,
+            hooks: None,
+            provenance: None,
+            neighbor_directions,
+            unresolved_set: state.unresolved.into_iter().collect(),
+            lowest_entropy_set: Vec::new(),
+            to_propagate: state.to_propagate.into(),
+            neighbors,
+            neighbor_states,
+            constraints: Vec::new(),
+            changed: state.changed,
+            narrowed: Vec::new(),
+            confined,
+            uninitialized: state.uninitialized,
+        }
+    }
+
+    /// Saves the progress of the solver, so that it can be continued later
+    /// with [Solver::resume].
+    pub fn save(&self) -> SolverState<Sp, Sp::Coordinate, R>
+    where
+        Sp: Clone,
+        R: Clone,
+    {
+        SolverState {
+            space: self.space.clone(),
+            unresolved: self.unresolved_set.iter().copied().collect(),
+            to_propagate: self.to_propagate.iter().copied().collect(),
+            changed: self.changed.clone(),
+            rng: self.rng.clone(),
+            observations: self.observations,
+            propagation_steps: self.propagation_steps,
+            confined: self
+                .confined
+                .as_ref()
+                .map(|cells| cells.iter().copied().collect()),
+            uninitialized: self.uninitialized,
+        }
+    }
+
+    /// The space being collapsed
+    pub fn space(&self) -> &Sp {
+        self.space
+    }
+
+    /// The number of cells observed so far
+    pub fn observations(&self) -> usize {
+        self.observations
+    }
+
+    /// The number of times the collapse rule has been applied to a cell while
+    /// propagating so far
+    pub fn propagation_steps(&self) -> usize {
+        self.propagation_steps
+    }
+
+    /// Sets the callbacks to notify as the solver runs, replacing any which
+    /// were set before
+    pub fn set_hooks(&mut self, hooks: impl CollapseHooks<St, Sp> + 'a) {
+        self.hooks = Some(Box::new(hooks));
+    }
+
+    /// Every recorded narrowing of the cell at `coord`, along with the
+    /// narrowings of other cells which led to them, in the order they
+    /// happened. Follows the neighbors which narrowed each cell back to the
+    /// observations that started it all.
+    ///
+    /// Empty unless [Solver::record_provenance] was called.
+    pub fn explain(&self, coord: Sp::Coordinate) -> Vec<Provenance<Sp::Coordinate, St>> {
+        self.provenance
+            .as_ref()
+            .map_or_else(Vec::new, |log| log.explain(coord))
+    }
+
+    /// Limits the solver to the cells in `region`. Cells outside of it are
+    /// never initialized, observed or narrowed by the collapse rule, but still
+    /// constrain the cells inside it as neighbors. Global constraints are only
+    /// given the changes inside the region. Confine the solver before adding
+    /// constraints or running it.
+    ///
+    /// Every cell in the region is checked against its neighbors again, so
+    /// that the cells around the region constrain it from the first step.
+    /// Resolved cells around the region are checked against the cells inside
+    /// it as well, failing with the inside cell which breaks their rule.
+    pub fn confine(&mut self, region: &(impl Region<Sp::Coordinate> + ?Sized)) {
+        let coordinates: Vec<Sp::Coordinate> = self
+            .space
+            .coordinate_list()
+            .iter()
+            .filter(|coord| region.contains(coord))
+            .copied()
+            .collect();
+        let cells: HashSet<Sp::Coordinate> = coordinates.iter().copied().collect();
+        self.unresolved_set.retain(|coord| cells.contains(coord));
+        self.to_propagate = coordinates.into();
+        self.total_cells = cells.len();
+        self.confined = Some(cells);
+    }
+
+    /// Adds a global constraint to the solver. The constraint is applied to
+    /// the whole space immediately, and again whenever cells are narrowed.
+    ///
+    /// Fails if the constraint can't be satisfied by the space.
+    pub fn add_constraint(
+        &mut self,
+        constraint: impl GlobalConstraint<St, Sp> + 'a,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        self.constraints.push(Box::new(constraint));
+        let coordinates = self.space.coordinate_list();
+        for coord in coordinates.iter() {
+            self.mark_changed(*coord);
+        }
+        self.propagate().map(drop)
+    }
+
+    /// Observes a single cell and propagates the result through the space.
+    ///
+    /// Returns `Ok(false)` once there are no unresolved cells left to observe.
+    pub fn step(&mut self) -> Result<bool, Contradiction<Sp::Coordinate>> {
+        Ok(self.advance()? == Step::Observed)
+    }
+
+    /// Steps the solver until every cell is resolved.
+    pub fn run(&mut self) -> Result<(), Contradiction<Sp::Coordinate>> {
+        while self.step()? {}
+        Ok(())
+    }
+
+    /// Steps the solver until every cell is resolved, or until `budget` runs
+    /// out. Limits on the number of observations and propagation steps count
+    /// from the start of this call.
+    ///
+    /// An interrupted run leaves the space partially collapsed, and can be
+    /// continued by running the solver again.
+    pub fn run_with_budget(
+        &mut self,
+        budget: &Budget,
+    ) -> Result<Status, Contradiction<Sp::Coordinate>> {
+        let mut limits = budget.clone();
+        limits.max_observations = budget
+            .max_observations
+            .map(|max| self.observations.saturating_add(max));
+        limits.max_propagation_steps = budget
+            .max_propagation_steps
+            .map(|max| self.propagation_steps.saturating_add(max));
+        self.limits = Some(limits);
+        let status = loop {
+            match self.advance() {
+                Ok(Step::Observed) => {}
+                Ok(Step::Complete) => break Ok(Status::Complete),
+                Ok(Step::Interrupted) => break Ok(Status::Interrupted),
+                Err(contradiction) => break Err(contradiction),
+            }
+        };
+        self.limits = None;
+        status
+    }
+
+    fn advance(&mut self) -> Result<Step, Contradiction<Sp::Coordinate>> {
+        if !self.propagate()? || self.out_of_observations() {
+            return Ok(Step::Interrupted);
+        }
+        let Some(to_collapse) = self.find_next_to_collapse() else {
+            if let Some(hooks) = &mut self.hooks {
+                hooks.completed();
+            }
+            return Ok(Step::Complete);
+        };
+        self.load_neighbors(to_collapse);
+        self.rule.observe(
+            to_collapse,
+            &mut self.space[to_collapse],
+            &self.neighbor_states[..],
+            &mut self.rng,
+        );
+        self.observations += 1;
+        if let Some(log) = &mut self.provenance {
+            log.record(to_collapse, &self.space[to_collapse], vec![Cause::Observed]);
+        }
+        if let Some(hooks) = &mut self.hooks {
+            hooks.observed(to_collapse, &self.space[to_collapse]);
+        }
+        if self.space[to_collapse].is_contradiction() {
+            let contradiction = Contradiction {
+                coordinate: to_collapse,
+            };
+            self.report_contradiction(&contradiction);
+            return Err(contradiction);
+        }
+        self.mark_changed(to_collapse);
+        if let Err(contradiction) = self.check_fixed_neighbors(to_collapse) {
+            self.report_contradiction(&contradiction);
+            return Err(contradiction);
+        }
+        self.queue_loaded_neighbors();
+        if !self.propagate()? {
+            return Ok(Step::Interrupted);
+        }
+        Ok(Step::Observed)
+    }
+
+    // Checked before every observation, so that a cancelled run stops even
+    // when propagation has nothing to do
+    fn out_of_observations(&self) -> bool {
+        self.limits.as_ref().is_some_and(|limits| {
+            limits
+                .max_observations
+                .is_some_and(|max| self.observations >= max)
+                || limits.is_cancelled()
+        })
+    }
+
+    fn out_of_propagation_steps(&self) -> bool {
+        self.limits.as_ref().is_some_and(|limits| {
+            limits
+                .max_propagation_steps
+                .is_some_and(|max| self.propagation_steps >= max)
+                || limits.is_cancelled()
+        })
+    }
+
+    fn find_next_to_collapse(&mut self) -> Option<Sp::Coordinate> {
+        let space = &*self.space;
+        self.unresolved_set
+            .retain(|unresolved| space[*unresolved].entropy() > 0);
+        if let Some(hooks) = &mut self.hooks {
+            hooks.progress(
+                self.total_cells - self.unresolved_set.len(),
+                self.total_cells,
+            );
+        }
+        let mut lowest_entropy = u32::MAX;
+        self.lowest_entropy_set.clear();
+        for unresolved in self.unresolved_set.iter() {
+            let entropy = space[*unresolved].entropy();
+            if entropy < lowest_entropy {
+                lowest_entropy = entropy;
+                self.lowest_entropy_set.clear();
+                self.lowest_entropy_set.push(*unresolved);
+            } else if entropy == lowest_entropy {
+                self.lowest_entropy_set.push(*unresolved);
+            }
+        }
+        if self.lowest_entropy_set.is_empty() {
+            None
+        } else {
+            let index = self.rng.gen_range(0..self.lowest_entropy_set.len());
+            Some(self.lowest_entropy_set[index])
+        }
+    }
+
+    // Fills the neighbor buffers with the neighbors of `coord` and their states
+    fn load_neighbors(&mut self, coord: Sp::Coordinate) {
+        self.space
+            .neighbors(coord, &self.neighbor_directions, &mut self.neighbors);
+        for i in 0..self.neighbor_directions.len() {
+            self.neighbor_states[i] = self.neighbors[i].map(|coord| self.space[coord].clone());
+        }
+    }
+
+    // Whether the solver may modify the cell at `coord`
+    fn is_free(&self, coord: &Sp::Coordinate) -> bool {
+        self.confined
+            .as_ref()
+            .is_none_or(|cells| cells.contains(coord))
+    }
+
+    // Records a narrowing of a cell by the collapse rule, finding the neighbors
+    // responsible by running the rule with each of them on its own
+    fn record_neighbor_causes(&mut self, coord: Sp::Coordinate, before: &St) {
+        let Some(log) = &self.provenance else {
+            return;
+        };
+        let mut causes = Vec::new();
+        let mut isolated = vec![None; self.neighbor_states.len()];
+        for (i, neighbor) in self.neighbors.iter().enumerate() {
+            let (Some(neighbor), Some(neighbor_state)) = (neighbor, &self.neighbor_states[i])
+            else {
+                continue;
+            };
+            isolated[i] = Some(neighbor_state.clone());
+            let mut allowed = before.clone();
+            self.rule.collapse(coord, &mut allowed, &isolated);
+            isolated[i] = None;
+            if allowed != *before {
+                causes.push(Cause::Neighbor {
+                    neighbor: *neighbor,
+                    offset_index: i,
+                    neighbor_state: neighbor_state.clone(),
+                    removed: log.removed(before, &allowed),
+                });
+            }
+        }
+        if causes.is_empty() {
+            causes.push(Cause::Neighbors);
+        }
+        if let Some(log) = &mut self.provenance {
+            log.record(coord, &self.space[coord], causes);
+        }
+    }
+
+    // Checks that the cells around `coord` which the solver may not modify
+    // still allow it, given the neighbors loaded for it. Rules don't have to be
+    // symmetric, so a fixed cell may rule out a state of its neighbor without
+    // the neighbor ruling out anything of the fixed cell's. Fixed cells which
+    // are still open only fail once they are left without any states.
+    fn check_fixed_neighbors(
+        &mut self,
+        coord: Sp::Coordinate,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        let Some(confined) = &self.confined else {
+            return Ok(());
+        };
+        if self
+            .neighbors
+            .iter()
+            .flatten()
+            .all(|neighbor| confined.contains(neighbor))
+        {
+            return Ok(());
+        }
+        let fixed: Vec<Sp::Coordinate> = self
+            .neighbors
+            .iter()
+            .flatten()
+            .filter(|neighbor| !confined.contains(neighbor))
+            .copied()
+            .collect();
+        let mut neighbors = vec![None; self.neighbor_directions.len()];
+        for fixed in fixed {
+            self.space
+                .neighbors(fixed, &self.neighbor_directions, &mut neighbors);
+            let states: Vec<Option<St>> = neighbors
+                .iter()
+                .map(|neighbor| neighbor.map(|neighbor| self.space[neighbor].clone()))
+                .collect();
+            let before = &self.space[fixed];
+            let mut cell = before.clone();
+            self.rule.collapse(fixed, &mut cell, &states);
+            if cell.is_contradiction() || (before.entropy() == 0 && cell != *before) {
+                return Err(Contradiction { coordinate: coord });
+            }
+        }
+        Ok(())
+    }
+
+    fn queue_loaded_neighbors(&mut self) {
+        for neighbor in self.neighbors.iter().flatten() {
+            if self.is_free(neighbor) {
+                self.to_propagate.push_back(*neighbor);
+            }
+        }
+    }
+
+    fn mark_changed(&mut self, coord: Sp::Coordinate) {
+        if !self.constraints.is_empty() && self.is_free(&coord) {
+            self.changed.push(coord);
+        }
+    }
+
+    // Queues a cell which was narrowed outside of propagation, along with its
+    // neighbors, so that the change is checked and spread by the rule
+    fn queue_narrowed(&mut self, coord: Sp::Coordinate) {
+        if !self.is_free(&coord) {
+            return;
+        }
+        self.mark_changed(coord);
+        self.to_propagate.push_back(coord);
+        self.space
+            .neighbors(coord, &self.neighbor_directions, &mut self.neighbors);
+        self.queue_loaded_neighbors();
+    }
+
+    // Runs the collapse rule and global constraints until neither of them
+    // narrow any more cells. Returns `Ok(false)` if propagation was
+    // interrupted by the budget before finishing.
+    fn propagate(&mut self) -> Result<bool, Contradiction<Sp::Coordinate>> {
+        let result = self.propagate_all();
+        if let Err(contradiction) = &result {
+            self.report_contradiction(contradiction);
+        }
+        result
+    }
+
+    // Lets the rule narrow the initial states of the free cells
+    fn initialize(&mut self) -> Result<(), Contradiction<Sp::Coordinate>> {
+        self.uninitialized = false;
+        for coord in self.space.coordinate_list().iter() {
+            if !self.is_free(coord) {
+                continue;
+            }
+            let cell = &mut self.space[*coord];
+            self.rule.initialize(*coord, cell);
+            if cell.is_contradiction() {
+                return Err(Contradiction { coordinate: *coord });
+            }
+        }
+        Ok(())
+    }
+
+    fn report_contradiction(&mut self, contradiction: &Contradiction<Sp::Coordinate>) {
+        if let Some(hooks) = &mut self.hooks {
+            hooks.contradiction(contradiction);
+        }
+    }
+
+    fn propagate_all(&mut self) -> Result<bool, Contradiction<Sp::Coordinate>> {
+        if self.uninitialized {
+            self.initialize()?;
+        }
+        loop {
+            if !self.propagate_rule()? {
+                return Ok(false);
+            }
+            if self.changed.is_empty() {
+                return Ok(true);
+            }
+            let changed = std::mem::take(&mut self.changed);
+            for constraint in self.constraints.iter_mut() {
+                constraint.propagate(self.space, &changed, &mut self.narrowed)?;
+            }
+            while let Some(narrowed) = self.narrowed.pop() {
+                if let Some(log) = &mut self.provenance {
+                    log.record(narrowed, &self.space[narrowed], vec![Cause::Constraint]);
+                }
+                if self.space[narrowed].is_contradiction() {
+                    self.narrowed.clear();
+                    return Err(Contradiction {
+                        coordinate: narrowed,
+                    });
+                }
+                self.queue_narrowed(narrowed);
+            }
+        }
+    }
+
+    fn propagate_rule(&mut self) -> Result<bool, Contradiction<Sp::Coordinate>> {
+        while !self.to_propagate.is_empty() {
+            if self.out_of_propagation_steps() {
+                return Ok(false);
+            }
+            let propagating = self.to_propagate.pop_front().unwrap();
+            if !self.is_free(&propagating) {
+                continue;
+            }
+            self.propagation_steps += 1;
+            let entropy_before = self.space[propagating].entropy();
+
+            // Resolved cells are still checked against their neighbors, so
+            // that conflicts with them are reported rather than ignored.
+            self.load_neighbors(propagating);
+            let before = self
+                .provenance
+                .is_some()
+                .then(|| self.space[propagating].clone());
+            self.rule.collapse(
+                propagating,
+                &mut self.space[propagating],
+                &self.neighbor_states[..],
+            );
+            if let Some(before) = before {
+                if self.space[propagating] != before {
+                    self.record_neighbor_causes(propagating, &before);
+                }
+            }
+            let cell = &self.space[propagating];
+
+            if cell.is_contradiction() {
+                self.to_propagate.clear();
+                return Err(Contradiction {
+                    coordinate: propagating,
+                });
+            }
+            let entropy_after = cell.entropy();
+            if entropy_after < entropy_before {
+                if let Err(contradiction) = self.check_fixed_neighbors(propagating) {
+                    self.to_propagate.clear();
+                    return Err(contradiction);
+                }
+                if let Some(hooks) = &mut self.hooks {
+                    hooks.narrowed(propagating, entropy_before, entropy_after);
+                }
+                self.mark_changed(propagating);
+                self.queue_loaded_neighbors();
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<'a, St: State + SetState, Sp: Space<St>, Rule: CollapseRule<St, Sp>, R: Rng>
+    Solver<'a, St, Sp, Rule, R>
+{
+    /// Forces the cell at `coord` into the final state `state` and propagates
+    /// the change immediately.
+    ///
+    /// Fails without changing the cell if `state` is not a single final
+    /// state, use [Solver::restrict] to narrow a cell to several states, or if
+    /// `state` is no longer possible for the cell. Also fails if pinning it
+    /// leaves another cell without any possible states.
+    pub fn pin(
+        &mut self,
+        coord: Sp::Coordinate,
+        state: &St,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        if state.entropy() != 0 || state.is_contradiction() {
+            return Err(Contradiction { coordinate: coord });
+        }
+        self.restrict(coord, state)
+    }
+
+    /// Starts recording why each cell is narrowed from here on, so that
+    /// contradictions can be traced back with [Solver::explain]. This slows
+    /// the solver down considerably, and is meant for debugging rules.
+    pub fn record_provenance(&mut self) {
+        self.provenance.get_or_insert_with(ProvenanceLog::new);
+    }
+
+    /// Removes every possible state of the cell at `coord` which is not in
+    /// `states`, and propagates the change immediately.
+    ///
+    /// Fails without changing the cell if none of `states` are possible for
+    /// it. Also fails if restricting it leaves another cell without any
+    /// possible states.
+    pub fn restrict(
+        &mut self,
+        coord: Sp::Coordinate,
+        states: &St,
+    ) -> Result<(), Contradiction<Sp::Coordinate>> {
+        if !self.space[coord].has_any_of(states) {
+            let contradiction = Contradiction { coordinate: coord };
+            self.report_contradiction(&contradiction);
+            return Err(contradiction);
+        }
+        self.space[coord].retain_states(states);
+        if let Some(log) = &mut self.provenance {
+            log.record(coord, &self.space[coord], vec![Cause::Restricted]);
+        }
+        self.queue_narrowed(coord);
+        self.propagate().map(drop)
+    }
+}
+
+#[derive(PartialEq)]
+enum Step {
+    Observed,
+    Complete,
+    Interrupted,
+}
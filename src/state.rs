@@ -4,4 +4,12 @@ pub trait State: Clone + PartialEq {
     /// final, and cannot be collapsed further, while higher values mean there
     /// are more possible values this state could collapse to.
     fn entropy(&self) -> u32;
+
+    /// Checks if this state has no possible values left. Such a cell can't be
+    /// resolved, and the collapse it is part of has failed.
+    ///
+    /// Defaults to `false` for state types which can't represent this.
+    fn is_contradiction(&self) -> bool {
+        false
+    }
 }
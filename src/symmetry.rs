@@ -0,0 +1,137 @@
+use std::fmt;
+
+use crate::cube_grid::CubeGrid;
+use crate::{Contradiction, GlobalConstraint, SetState, State};
+
+/// The ways a [CubeGrid] can be symmetric
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Symmetry {
+    /// Mirrored across the middle of the x axis
+    MirrorX,
+    /// Mirrored across the middle of the y axis
+    MirrorY,
+    /// Mirrored across the middle of the z axis
+    MirrorZ,
+    /// Rotated half a turn around the vertical axis through the center
+    Rotate180,
+    /// Rotated a quarter turn around the vertical axis through the center.
+    /// Only possible when the grid's width and length are the same.
+    Rotate90,
+}
+
+impl Symmetry {
+    // The cell which `coord` maps to in a grid of the given size
+    fn map(
+        &self,
+        (width, height, length): (isize, isize, isize),
+        (x, y, z): (isize, isize, isize),
+    ) -> (isize, isize, isize) {
+        match self {
+            Self::MirrorX => (width - 1 - x, y, z),
+            Self::MirrorY => (x, height - 1 - y, z),
+            Self::MirrorZ => (x, y, length - 1 - z),
+            Self::Rotate180 => (width - 1 - x, y, length - 1 - z),
+            Self::Rotate90 => (length - 1 - z, y, x),
+        }
+    }
+}
+
+/// Error produced by [SymmetryConstraint::new] when a grid of the given size
+/// can't have the symmetry
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnsupportedSymmetry {
+    pub symmetry: Symmetry,
+    pub size: (isize, isize, isize),
+}
+
+impl fmt::Display for UnsupportedSymmetry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} is not possible on a grid of size {:?}",
+            self.symmetry, self.size
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedSymmetry {}
+
+/// Global constraint which makes a [CubeGrid] symmetric.
+///
+/// Every cell is tied to its image under the symmetry, which has to be the
+/// mapped variant of the cell's tile, such as the mirrored version of a corner
+/// piece. Whenever a cell narrows, so does its image, and the collapse rule
+/// then propagates from both of them.
+///
+/// With [Symmetry::Rotate90], cells are tied around a cycle of four. The
+/// mapping should rotate tiles a quarter turn in the same direction as the
+/// cells, which is from +x towards +z.
+pub struct SymmetryConstraint<S, F> {
+    symmetry: Symmetry,
+    size: (isize, isize, isize),
+    tile_map: F,
+    mapped: Vec<S>,
+}
+
+impl<S: SetState + State, F: Fn(&S) -> S> SymmetryConstraint<S, F> {
+    /// * `symmetry` - The symmetry to enforce
+    /// * `grid` - The grid being collapsed
+    /// * `tile_map` - Maps a final state to its variant under the symmetry.
+    ///   States which map to themselves are the only ones allowed on the
+    ///   cells which the symmetry maps onto themselves.
+    ///
+    /// Fails if the grid can't have the symmetry, which is when a quarter
+    /// turn is asked for on a grid which isn't as wide as it is long.
+    pub fn new(
+        symmetry: Symmetry,
+        grid: &CubeGrid<S>,
+        tile_map: F,
+    ) -> Result<Self, UnsupportedSymmetry> {
+        let size = (grid.width(), grid.height(), grid.length());
+        if symmetry == Symmetry::Rotate90 && grid.width() != grid.length() {
+            return Err(UnsupportedSymmetry { symmetry, size });
+        }
+        Ok(Self {
+            symmetry,
+            size,
+            tile_map,
+            mapped: Vec::new(),
+        })
+    }
+}
+
+impl<S: SetState + State + 'static, F: Fn(&S) -> S> GlobalConstraint<S, CubeGrid<S>>
+    for SymmetryConstraint<S, F>
+{
+    fn propagate(
+        &mut self,
+        space: &mut CubeGrid<S>,
+        changed: &[(isize, isize, isize)],
+        narrowed: &mut Vec<(isize, isize, isize)>,
+    ) -> Result<(), Contradiction<(isize, isize, isize)>> {
+        for coord in changed {
+            let cell = &space[*coord];
+            self.mapped.clear();
+            cell.collect_final_states(&mut self.mapped);
+            let mut allowed = cell.clone();
+            allowed.clear_states(cell);
+            let image = self.symmetry.map(self.size, *coord);
+            for state in self.mapped.iter() {
+                let mapped = (self.tile_map)(state);
+                // A cell which is its own image can only hold states which
+                // are their own variant
+                if image != *coord || mapped == *state {
+                    allowed.set_states(&mapped);
+                }
+            }
+
+            let image_cell = &mut space[image];
+            let entropy = image_cell.entropy();
+            image_cell.retain_states(&allowed);
+            if image_cell.entropy() < entropy || image_cell.is_contradiction() {
+                narrowed.push(image);
+            }
+        }
+        Ok(())
+    }
+}
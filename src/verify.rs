@@ -0,0 +1,107 @@
+use std::fmt;
+
+use crate::{CollapseRule, Space, State};
+
+/// A way in which a collapsed space breaks its collapse rule, as found by
+/// [verify]
+#[derive(Clone, PartialEq, Debug)]
+pub enum Violation<C, S> {
+    /// The cell hasn't resolved to a single final state
+    Unresolved { coordinate: C, state: S },
+    /// The cell's state isn't allowed at its position
+    Disallowed { coordinate: C, state: S },
+    /// The cell's state isn't allowed next to the state of `neighbor`
+    Incompatible {
+        coordinate: C,
+        state: S,
+        neighbor: C,
+        neighbor_state: S,
+    },
+}
+
+impl<C: fmt::Debug, S: fmt::Debug> fmt::Display for Violation<C, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unresolved { coordinate, state } => {
+                write!(f, "cell {:?} is unresolved: {:?}", coordinate, state)
+            }
+            Self::Disallowed { coordinate, state } => {
+                write!(f, "cell {:?} can't be {:?}", coordinate, state)
+            }
+            Self::Incompatible {
+                coordinate,
+                state,
+                neighbor,
+                neighbor_state,
+            } => write!(
+                f,
+                "cell {:?} can't be {:?} next to cell {:?} being {:?}",
+                coordinate, state, neighbor, neighbor_state
+            ),
+        }
+    }
+}
+
+/// Checks that a collapsed space satisfies `rule`: every cell has to be final,
+/// and allowed by the rule next to each of its neighbors.
+///
+/// Each neighbor is checked on its own, by running the rule on the cell with
+/// only that neighbor present. This finds every broken pair for rules which
+/// look at their neighbors independently, such as
+/// [crate::set_rule::SetCollapseRule].
+///
+/// Returns every violation found, in the order of the space's coordinates.
+pub fn verify<Rule: CollapseRule<St, Sp>, St: State, Sp: Space<St>>(
+    space: &Sp,
+    rule: &Rule,
+) -> Result<(), Vec<Violation<Sp::Coordinate, St>>> {
+    let offsets = rule.neighbor_offsets();
+    let mut neighbors = vec![None; offsets.len()];
+    let mut isolated = vec![None; offsets.len()];
+    let mut violations = Vec::new();
+
+    for coord in space.coordinate_list().iter() {
+        let cell = &space[*coord];
+        if cell.entropy() != 0 || cell.is_contradiction() {
+            violations.push(Violation::Unresolved {
+                coordinate: *coord,
+                state: cell.clone(),
+            });
+            continue;
+        }
+
+        let mut checked = cell.clone();
+        rule.initialize(*coord, &mut checked);
+        if checked != *cell {
+            violations.push(Violation::Disallowed {
+                coordinate: *coord,
+                state: cell.clone(),
+            });
+        }
+
+        space.neighbors(*coord, &offsets, &mut neighbors);
+        for (i, neighbor) in neighbors.iter().enumerate() {
+            let Some(neighbor) = neighbor else {
+                continue;
+            };
+            isolated[i] = Some(space[*neighbor].clone());
+            let mut checked = cell.clone();
+            rule.collapse(*coord, &mut checked, &isolated);
+            if checked != *cell {
+                violations.push(Violation::Incompatible {
+                    coordinate: *coord,
+                    state: cell.clone(),
+                    neighbor: *neighbor,
+                    neighbor_state: space[*neighbor].clone(),
+                });
+            }
+            isolated[i] = None;
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(violations)
+    }
+}
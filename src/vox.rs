@@ -0,0 +1,430 @@
+//! Reading and writing [MagicaVoxel](https://ephtracy.github.io/) `.vox`
+//! files.
+//!
+//! MagicaVoxel uses a z-up coordinate system, while [CubeGrid] is y-up. The y
+//! and z axes are swapped on the way in and out, so a grid viewed from above
+//! looks the same in both.
+
+use bevy_utils::HashMap;
+use std::io::{self, Read, Write};
+
+use crate::cube_grid::{grid_size, CubeGrid};
+
+/// The largest size of a single model along each axis. Larger grids are
+/// written as several models next to each other.
+pub const MAX_MODEL_SIZE: isize = 256;
+
+// The most cells a grid read from a `.vox` file may have, so that a file with
+// models placed far apart can't exhaust memory
+const MAX_CELLS: usize = 1 << 30;
+
+/// The colors of a `.vox` file as RGBA, indexed by palette index. Index 0
+/// stands for empty space, so its color is unused.
+pub type VoxPalette = [[u8; 4]; 256];
+
+/// The contents of a `.vox` file read by [read_vox]
+///
+/// * `grid` - The palette index of each voxel, with 0 for empty space
+/// * `palette` - The colors of the palette indices, or `None` if the file
+///   uses MagicaVoxel's default palette
+#[derive(Clone, Debug)]
+pub struct Vox {
+    pub grid: CubeGrid<u8>,
+    pub palette: Option<Box<VoxPalette>>,
+}
+
+/// Writes a grid as a `.vox` file.
+///
+/// * `grid` - The grid to write
+/// * `color_index` - The palette index of a cell's state, or 0 to leave the
+///   cell empty
+/// * `palette` - The colors of the palette indices, or `None` to use
+///   MagicaVoxel's default palette
+/// * `writer` - Where to write the file
+pub fn write_vox<T: 'static>(
+    grid: &CubeGrid<T>,
+    color_index: impl Fn(&T) -> u8,
+    palette: Option<&VoxPalette>,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    // Model sizes and offsets, in MagicaVoxel's axes
+    let size = (grid.width(), grid.length(), grid.height());
+    if size.0 < 1 || size.1 < 1 || size.2 < 1 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "can't write an empty grid as a .vox file",
+        ));
+    }
+    let mut models = Vec::new();
+    for oz in (0..size.2).step_by(MAX_MODEL_SIZE as usize) {
+        for oy in (0..size.1).step_by(MAX_MODEL_SIZE as usize) {
+            for ox in (0..size.0).step_by(MAX_MODEL_SIZE as usize) {
+                let model_size = (
+                    (size.0 - ox).min(MAX_MODEL_SIZE),
+                    (size.1 - oy).min(MAX_MODEL_SIZE),
+                    (size.2 - oz).min(MAX_MODEL_SIZE),
+                );
+                models.push(((ox, oy, oz), model_size));
+            }
+        }
+    }
+
+    let mut content = Vec::new();
+    for &((ox, oy, oz), (sx, sy, sz)) in models.iter() {
+        let mut voxels = Vec::new();
+        for z in 0..sz {
+            for y in 0..sy {
+                for x in 0..sx {
+                    let index = color_index(&grid[(ox + x, oz + z, oy + y)]);
+                    if index != 0 {
+                        voxels.extend([x as u8, y as u8, z as u8, index]);
+                    }
+                }
+            }
+        }
+        let mut xyzi = ((voxels.len() / 4) as u32).to_le_bytes().to_vec();
+        xyzi.extend(voxels);
+        let mut model_size = Vec::new();
+        for dimension in [sx, sy, sz] {
+            model_size.extend((dimension as u32).to_le_bytes());
+        }
+        write_chunk(&mut content, b"SIZE", &model_size);
+        write_chunk(&mut content, b"XYZI", &xyzi);
+    }
+
+    // A scene graph placing each model at its offset: a root transform, a
+    // group, and a transform and shape for each model
+    if models.len() > 1 {
+        let mut node = Vec::new();
+        write_node_header(&mut node, 0);
+        write_transform(&mut node, 1, -1, None);
+        write_chunk(&mut content, b"nTRN", &node);
+
+        let mut node = Vec::new();
+        write_node_header(&mut node, 1);
+        node.extend((models.len() as u32).to_le_bytes());
+        for i in 0..models.len() {
+            node.extend((2 + 2 * i as i32).to_le_bytes());
+        }
+        write_chunk(&mut content, b"nGRP", &node);
+
+        for (i, &((ox, oy, oz), (sx, sy, sz))) in models.iter().enumerate() {
+            // Translations are to the center of the model
+            let translation = (ox + sx / 2, oy + sy / 2, oz + sz / 2);
+            let mut node = Vec::new();
+            write_node_header(&mut node, 2 + 2 * i as i32);
+            write_transform(&mut node, 3 + 2 * i as i32, 0, Some(translation));
+            write_chunk(&mut content, b"nTRN", &node);
+
+            let mut node = Vec::new();
+            write_node_header(&mut node, 3 + 2 * i as i32);
+            node.extend(1u32.to_le_bytes());
+            node.extend((i as u32).to_le_bytes());
+            write_dict(&mut node, &[]);
+            write_chunk(&mut content, b"nSHP", &node);
+        }
+    }
+
+    if let Some(palette) = palette {
+        // Entry i of the chunk is the color of palette index i + 1
+        let mut rgba = Vec::new();
+        for color in palette[1..].iter().chain(std::iter::once(&palette[0])) {
+            rgba.extend(color);
+        }
+        write_chunk(&mut content, b"RGBA", &rgba);
+    }
+
+    writer.write_all(b"VOX ")?;
+    writer.write_all(&150u32.to_le_bytes())?;
+    writer.write_all(b"MAIN")?;
+    writer.write_all(&0u32.to_le_bytes())?;
+    writer.write_all(&(content.len() as u32).to_le_bytes())?;
+    writer.write_all(&content)?;
+    writer.flush()
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: &[u8]) {
+    out.extend(id);
+    out.extend((content.len() as u32).to_le_bytes());
+    out.extend(0u32.to_le_bytes());
+    out.extend(content);
+}
+
+fn write_string(out: &mut Vec<u8>, string: &str) {
+    out.extend((string.len() as u32).to_le_bytes());
+    out.extend(string.as_bytes());
+}
+
+fn write_dict(out: &mut Vec<u8>, entries: &[(&str, &str)]) {
+    out.extend((entries.len() as u32).to_le_bytes());
+    for (key, value) in entries {
+        write_string(out, key);
+        write_string(out, value);
+    }
+}
+
+// Node id and empty attributes
+fn write_node_header(out: &mut Vec<u8>, id: i32) {
+    out.extend(id.to_le_bytes());
+    write_dict(out, &[]);
+}
+
+fn write_transform(
+    out: &mut Vec<u8>,
+    child: i32,
+    layer: i32,
+    translation: Option<(isize, isize, isize)>,
+) {
+    out.extend(child.to_le_bytes());
+    out.extend((-1i32).to_le_bytes());
+    out.extend(layer.to_le_bytes());
+    out.extend(1u32.to_le_bytes());
+    match translation {
+        Some((x, y, z)) => write_dict(out, &[("_t", &format!("{} {} {}", x, y, z))]),
+        None => write_dict(out, &[]),
+    }
+}
+
+// A node of the scene graph
+enum Node {
+    Transform {
+        child: i32,
+        translation: (isize, isize, isize),
+    },
+    Group(Vec<i32>),
+    Shape(Vec<usize>),
+}
+
+/// Reads a `.vox` file into a grid of palette indices.
+///
+/// Files with several models are combined into one grid, using the
+/// translations of the scene graph to place the models. Rotations are
+/// ignored. The grid starts at the lowest corner of any model.
+///
+/// Fails on malformed files, including models larger than [MAX_MODEL_SIZE]
+/// and models placed so far apart that the grid would be unreasonably large.
+pub fn read_vox(mut reader: impl Read) -> io::Result<Vox> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    let mut bytes = Bytes(&data);
+    if bytes.take(4)? != b"VOX " {
+        return Err(invalid("not a .vox file"));
+    }
+    bytes.u32()?;
+    if bytes.take(4)? != b"MAIN" {
+        return Err(invalid("missing MAIN chunk"));
+    }
+    bytes.u32()?;
+    let children = bytes.u32()? as usize;
+    let mut bytes = Bytes(bytes.take(children)?);
+
+    let mut models = Vec::new();
+    let mut size = None;
+    let mut nodes = HashMap::new();
+    let mut palette = None;
+    while !bytes.0.is_empty() {
+        let id = bytes.take(4)?;
+        let content_size = bytes.u32()? as usize;
+        let children_size = bytes.u32()? as usize;
+        let mut content = Bytes(bytes.take(content_size)?);
+        bytes.take(children_size)?;
+        match id {
+            b"SIZE" => {
+                let model_size = (content.u32()?, content.u32()?, content.u32()?);
+                let valid = |dimension: u32| (1..=MAX_MODEL_SIZE as u32).contains(&dimension);
+                if !(valid(model_size.0) && valid(model_size.1) && valid(model_size.2)) {
+                    return Err(invalid("invalid model size"));
+                }
+                size = Some(model_size);
+            }
+            b"XYZI" => {
+                let size = size
+                    .take()
+                    .ok_or_else(|| invalid("XYZI chunk without SIZE"))?;
+                let count = content.u32()? as usize;
+                let length = count
+                    .checked_mul(4)
+                    .ok_or_else(|| invalid("unexpected end of .vox file"))?;
+                let voxels = content.take(length)?.to_vec();
+                models.push((size, voxels));
+            }
+            b"RGBA" => {
+                let mut colors = Box::new([[0; 4]; 256]);
+                for i in 0..255 {
+                    colors[i + 1].copy_from_slice(content.take(4)?);
+                }
+                palette = Some(colors);
+            }
+            b"nTRN" => {
+                let id = content.i32()?;
+                content.dict()?;
+                let child = content.i32()?;
+                content.take(8)?;
+                let frames = content.u32()?;
+                let mut translation = (0, 0, 0);
+                for frame in 0..frames {
+                    let attributes = content.dict()?;
+                    if let (0, Some(t)) = (frame, attributes.get("_t")) {
+                        translation = parse_translation(t)?;
+                    }
+                }
+                nodes.insert(id, Node::Transform { child, translation });
+            }
+            b"nGRP" => {
+                let id = content.i32()?;
+                content.dict()?;
+                let count = content.u32()?;
+                let children = (0..count)
+                    .map(|_| content.i32())
+                    .collect::<io::Result<_>>()?;
+                nodes.insert(id, Node::Group(children));
+            }
+            b"nSHP" => {
+                let id = content.i32()?;
+                content.dict()?;
+                let count = content.u32()?;
+                let mut shapes = Vec::new();
+                for _ in 0..count {
+                    shapes.push(content.u32()? as usize);
+                    content.dict()?;
+                }
+                nodes.insert(id, Node::Shape(shapes));
+            }
+            _ => {}
+        }
+    }
+    if models.is_empty() {
+        return Err(invalid("no models in .vox file"));
+    }
+
+    // The lowest corner of each model, in MagicaVoxel's axes
+    let mut placed = Vec::new();
+    if nodes.is_empty() {
+        placed.extend((0..models.len()).map(|model| (model, (0, 0, 0))));
+    } else {
+        let mut stack = vec![(0, (0, 0, 0), 0)];
+        while let Some((id, (x, y, z), depth)) = stack.pop() {
+            if depth > nodes.len() {
+                return Err(invalid("cycle in scene graph"));
+            }
+            match nodes.get(&id) {
+                Some(Node::Transform { child, translation }) => {
+                    let (tx, ty, tz) = *translation;
+                    let translated = (offset(x, tx)?, offset(y, ty)?, offset(z, tz)?);
+                    stack.push((*child, translated, depth + 1));
+                }
+                Some(Node::Group(children)) => {
+                    stack.extend(children.iter().map(|&child| (child, (x, y, z), depth + 1)));
+                }
+                Some(Node::Shape(shapes)) => {
+                    for &model in shapes {
+                        let ((sx, sy, sz), _) = models
+                            .get(model)
+                            .ok_or_else(|| invalid("shape refers to missing model"))?;
+                        let (sx, sy, sz) = (*sx as isize, *sy as isize, *sz as isize);
+                        let corner = (
+                            offset(x, -sx / 2)?,
+                            offset(y, -sy / 2)?,
+                            offset(z, -sz / 2)?,
+                        );
+                        placed.push((model, corner));
+                    }
+                }
+                None => return Err(invalid("scene graph refers to missing node")),
+            }
+        }
+        // Later models are drawn over earlier ones
+        placed.sort_by_key(|(model, _)| *model);
+    }
+
+    let mut min = (isize::MAX, isize::MAX, isize::MAX);
+    let mut max = (isize::MIN, isize::MIN, isize::MIN);
+    for (model, (x, y, z)) in placed.iter() {
+        let ((sx, sy, sz), _) = models[*model];
+        min = (min.0.min(*x), min.1.min(*y), min.2.min(*z));
+        max = (
+            max.0.max(offset(*x, sx as isize)?),
+            max.1.max(offset(*y, sy as isize)?),
+            max.2.max(offset(*z, sz as isize)?),
+        );
+    }
+    let size = (
+        offset(max.0, min.0.checked_neg().ok_or_else(out_of_range)?)?,
+        offset(max.1, min.1.checked_neg().ok_or_else(out_of_range)?)?,
+        offset(max.2, min.2.checked_neg().ok_or_else(out_of_range)?)?,
+    );
+    match grid_size(size.0, size.1, size.2) {
+        Some(cells) if cells <= MAX_CELLS => {}
+        _ => return Err(invalid("models are spread over too large a grid")),
+    }
+    let mut grid = CubeGrid::new(size.0, size.1, size.2, |_, _, _| 0);
+    for (model, (x, y, z)) in placed {
+        let ((sx, sy, sz), voxels) = &models[model];
+        for voxel in voxels.chunks_exact(4) {
+            let (vx, vy, vz) = (voxel[0] as u32, voxel[1] as u32, voxel[2] as u32);
+            if vx < *sx && vy < *sy && vz < *sz {
+                let (gx, gy, gz) = (x + vx as isize, y + vy as isize, z + vz as isize);
+                grid[(gx - min.0, gz - min.2, gy - min.1)] = voxel[3];
+            }
+        }
+    }
+    Ok(Vox { grid, palette })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn out_of_range() -> io::Error {
+    invalid("model placed out of range")
+}
+
+// Moves a coordinate by `by`, failing instead of overflowing
+fn offset(coord: isize, by: isize) -> io::Result<isize> {
+    coord.checked_add(by).ok_or_else(out_of_range)
+}
+
+fn parse_translation(translation: &str) -> io::Result<(isize, isize, isize)> {
+    let values: Vec<isize> = translation
+        .split_whitespace()
+        .map(|v| v.parse().map_err(|_| invalid("invalid translation")))
+        .collect::<io::Result<_>>()?;
+    match values[..] {
+        [x, y, z] => Ok((x, y, z)),
+        _ => Err(invalid("invalid translation")),
+    }
+}
+
+// The unread part of a chunk
+struct Bytes<'a>(&'a [u8]);
+
+impl<'a> Bytes<'a> {
+    fn take(&mut self, count: usize) -> io::Result<&'a [u8]> {
+        if count > self.0.len() {
+            return Err(invalid("unexpected end of .vox file"));
+        }
+        let (taken, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let length = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+
+    fn dict(&mut self) -> io::Result<HashMap<String, String>> {
+        let count = self.u32()?;
+        (0..count)
+            .map(|_| Ok((self.string()?, self.string()?)))
+            .collect()
+    }
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use bevy_utils::{Duration, Instant};
+use common::*;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use wfc3d::closure_rule::ClosureRule;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::set_rule::UniformSetCollapseObserver;
+use wfc3d::{Budget, CollapseRule, Solver, Status};
+
+fn run_until_interrupted(rule: &impl CollapseRule<Cell, CubeGrid<Cell>>, budget: Budget) {
+    let mut space = CubeGrid::new(4, 4, 4, |_, _, _| all(3));
+    let mut solver = Solver::new(&mut space, rule).unwrap();
+    // Propagates the initial states, leaving nothing queued
+    let propagate_only = Budget::new().max_observations(0);
+    assert_eq!(
+        solver.run_with_budget(&propagate_only),
+        Ok(Status::Interrupted)
+    );
+    assert_eq!(solver.run_with_budget(&budget), Ok(Status::Interrupted));
+    assert_eq!(solver.observations(), 0);
+}
+
+#[test]
+fn cancelled_run_observes_nothing() {
+    let cancelled = || Budget::new().cancel(Arc::new(AtomicBool::new(true)));
+    run_until_interrupted(&step_rule(3), cancelled());
+
+    // Nothing is ever propagated with a rule without neighbors
+    let unconstrained = ClosureRule::new(
+        &[],
+        |_: &(isize, isize, isize), _: &mut Cell, _: &[Option<Cell>]| {},
+        UniformSetCollapseObserver,
+    );
+    run_until_interrupted(&unconstrained, cancelled());
+}
+
+#[test]
+fn past_deadline_observes_nothing() {
+    let past = Instant::now() - Duration::from_secs(1);
+    run_until_interrupted(&step_rule(3), Budget::new().deadline(past));
+}
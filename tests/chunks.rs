@@ -0,0 +1,67 @@
+mod common;
+
+use common::*;
+use wfc3d::chunks::ChunkGenerator;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::verify;
+
+// Joins the chunks from (0, 0, 0) up to but not including `chunks` into a
+// single grid
+fn join(
+    generator: &ChunkGenerator<Cell, StepRule>,
+    chunk_size: (isize, isize, isize),
+    chunks: (isize, isize, isize),
+) -> CubeGrid<Cell> {
+    let (sx, sy, sz) = chunk_size;
+    CubeGrid::new(chunks.0 * sx, chunks.2 * sz, chunks.1 * sy, |x, y, z| {
+        generator.cell((x, y, z)).unwrap().clone()
+    })
+}
+
+#[test]
+fn chunks_continue_seamlessly() {
+    let rule = step_rule(5);
+    let chunk_size = (4, 3, 4);
+    for seed in 0..5 {
+        let mut generator = ChunkGenerator::new(&rule, all(5), chunk_size, seed);
+        for chunk in [
+            (0, 0, 0),
+            (1, 0, 0),
+            (0, 1, 0),
+            (1, 1, 0),
+            (0, 0, 1),
+            (1, 1, 1),
+            (1, 0, 1),
+            (0, 1, 1),
+        ] {
+            generator.generate(chunk).unwrap();
+        }
+        let world = join(&generator, chunk_size, (2, 2, 2));
+        assert_eq!(verify(&world, &rule), Ok(()), "seed {}", seed);
+    }
+}
+
+#[test]
+fn seams_keep_the_rules_of_existing_chunks() {
+    // A 0 at the end of the first chunk needs a 0 at the start of the second
+    let rule = asymmetric_rule();
+    let chunk_size = (3, 1, 1);
+    for seed in 0..20 {
+        let mut generator = ChunkGenerator::new(&rule, all(2), chunk_size, seed);
+        generator.generate((0, 0, 0)).unwrap();
+        generator.generate((1, 0, 0)).unwrap();
+        let world = join(&generator, chunk_size, (2, 1, 1));
+        assert_eq!(verify(&world, &rule), Ok(()), "seed {}", seed);
+    }
+}
+
+#[test]
+fn chunks_are_generated_the_same_every_time() {
+    let rule = step_rule(5);
+    let generate = || {
+        let mut generator = ChunkGenerator::new(&rule, all(5), (4, 3, 4), 3);
+        generator.generate((0, 0, 0)).unwrap();
+        values(generator.generate((-1, 0, 0)).unwrap())
+    };
+    assert_eq!(generate(), generate());
+}
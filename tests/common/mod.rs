@@ -0,0 +1,80 @@
+#![allow(dead_code)]
+
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::hashset_state::HashsetState;
+use wfc3d::set_rule::*;
+
+pub type Cell = HashsetState<u8>;
+pub type StepRule = SetCollapseRule<Cell, CubeGrid<Cell>, UniformSetCollapseObserver>;
+
+pub const AXES: [(isize, isize, isize); 6] = [
+    (1, 0, 0),
+    (-1, 0, 0),
+    (0, 1, 0),
+    (0, -1, 0),
+    (0, 0, 1),
+    (0, 0, -1),
+];
+
+/// Every state from 0 up to but not including `states`
+pub fn all(states: u8) -> Cell {
+    Cell::new(&(0..states).collect::<Vec<_>>())
+}
+
+/// A rule allowing neighbors along the axes to differ by at most one
+pub fn step_rule(states: u8) -> StepRule {
+    let mut builder = SetCollapseRuleBuilder::new(UniformSetCollapseObserver, all(states));
+    for s in 0..states {
+        let near: Vec<u8> = (0..states).filter(|t| t.abs_diff(s) <= 1).collect();
+        let neighbors: Vec<_> = AXES.iter().map(|d| (*d, Cell::new(&near))).collect();
+        builder = builder.allow(&Cell::new_final(&s), &neighbors);
+    }
+    builder.build()
+}
+
+/// A rule which allows any neighbors
+pub fn permissive_rule(states: u8) -> StepRule {
+    let mut builder = SetCollapseRuleBuilder::new(UniformSetCollapseObserver, all(states));
+    for s in 0..states {
+        let neighbors: Vec<_> = AXES.iter().map(|d| (*d, all(states))).collect();
+        builder = builder.allow(&Cell::new_final(&s), &neighbors);
+    }
+    builder.build()
+}
+
+/// The final value of each cell, in grid order
+pub fn values(grid: &CubeGrid<Cell>) -> Vec<u8> {
+    let mut values = Vec::new();
+    for y in 0..grid.height() {
+        for z in 0..grid.length() {
+            for x in 0..grid.width() {
+                values.push(*grid[(x, y, z)].hashset.iter().next().unwrap());
+            }
+        }
+    }
+    values
+}
+
+/// A two state rule where 0 needs another 0 on its +x side, while 1 allows
+/// any neighbors. The rule isn't symmetric, as 1 doesn't rule out a 0 on its
+/// -x side.
+pub fn asymmetric_rule() -> StepRule {
+    let zero: Vec<_> = AXES
+        .iter()
+        .map(|d| {
+            (
+                *d,
+                if *d == (1, 0, 0) {
+                    Cell::new_final(&0)
+                } else {
+                    all(2)
+                },
+            )
+        })
+        .collect();
+    let one: Vec<_> = AXES.iter().map(|d| (*d, all(2))).collect();
+    SetCollapseRuleBuilder::new(UniformSetCollapseObserver, all(2))
+        .allow(&Cell::new_final(&0), &zero)
+        .allow(&Cell::new_final(&1), &one)
+        .build()
+}
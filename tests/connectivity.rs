@@ -0,0 +1,60 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wfc3d::connectivity::{walkable_regions, ConnectivityConstraint};
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::Solver;
+
+#[test]
+fn walkable_cells_form_one_region() {
+    let rule = permissive_rule(2);
+    let walkable = Cell::new_final(&1);
+    for seed in 0..20 {
+        let mut space = CubeGrid::new(6, 6, 1, |_, _, _| all(2));
+        let mut solver = Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed)).unwrap();
+        solver
+            .add_constraint(ConnectivityConstraint::new(&walkable, &AXES))
+            .unwrap();
+        solver.run().unwrap();
+        drop(solver);
+        assert!(
+            walkable_regions(&space, &walkable, &AXES).len() <= 1,
+            "seed {}",
+            seed
+        );
+    }
+}
+
+#[test]
+fn cells_between_walkable_cells_become_walkable() {
+    let rule = permissive_rule(2);
+    let walkable = Cell::new_final(&1);
+    let mut space = CubeGrid::new(5, 1, 1, |x, _, _| {
+        if x == 0 || x == 4 {
+            walkable.clone()
+        } else {
+            all(2)
+        }
+    });
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    solver
+        .add_constraint(ConnectivityConstraint::new(&walkable, &AXES))
+        .unwrap();
+    solver.run().unwrap();
+    drop(solver);
+    assert_eq!(values(&space), [1, 1, 1, 1, 1]);
+}
+
+#[test]
+fn split_walkable_cells_fail() {
+    let rule = permissive_rule(2);
+    let walkable = Cell::new_final(&1);
+    let mut space = CubeGrid::new(3, 1, 1, |x, _, _| Cell::new_final(&[1, 0, 1][x as usize]));
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    let result = solver
+        .add_constraint(ConnectivityConstraint::new(&walkable, &AXES))
+        .and_then(|_| solver.run());
+    assert!(result.is_err());
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wfc3d::count_constraint::CountConstraint;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::{Contradiction, Final, GlobalConstraint, Solver};
+
+#[test]
+fn too_many_blames_a_resolved_cell() {
+    let mut space = CubeGrid::new(3, 1, 1, |x, _, _| Cell::new_final(&[2, 2, 0][x as usize]));
+    let mut constraint = CountConstraint::new(&Cell::new_final(&2), ..=1);
+    let changed = [(0, 0, 0), (1, 0, 0), (2, 0, 0)];
+    let result = constraint.propagate(&mut space, &changed, &mut Vec::new());
+    assert_eq!(
+        result,
+        Err(Contradiction {
+            coordinate: (1, 0, 0)
+        })
+    );
+}
+
+#[test]
+fn nothing_changed_is_not_a_contradiction() {
+    let mut space = CubeGrid::new(2, 1, 1, |_, _, _| all(3));
+    let mut constraint = CountConstraint::new(&Cell::new_final(&2), 1..=1);
+    assert_eq!(
+        constraint.propagate(&mut space, &[], &mut Vec::new()),
+        Ok(())
+    );
+}
+
+#[test]
+fn counts_sets_of_states() {
+    let rule = permissive_rule(3);
+    let counted = Cell::new(&[1, 2]);
+    for seed in 0..20 {
+        let mut space = CubeGrid::new(4, 1, 1, |_, _, _| all(3));
+        let mut solver = Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed)).unwrap();
+        solver
+            .add_constraint(CountConstraint::new(&counted, 2..=2))
+            .unwrap();
+        solver.restrict((0, 0, 0), &Cell::new_final(&0)).unwrap();
+        solver.restrict((1, 0, 0), &Cell::new_final(&0)).unwrap();
+        solver.run().unwrap();
+        drop(solver);
+
+        for x in 2..4 {
+            let value: Option<u8> = space[(x, 0, 0)].get();
+            assert!(matches!(value, Some(1 | 2)), "seed {}", seed);
+        }
+    }
+}
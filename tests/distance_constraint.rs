@@ -0,0 +1,66 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wfc3d::cube_grid::{manhattan_offsets, CubeGrid};
+use wfc3d::distance_constraint::{ExclusionConstraint, RequirementConstraint};
+use wfc3d::Solver;
+
+#[test]
+fn exclusion_holds_whichever_state_resolves_first() {
+    let rule = permissive_rule(10);
+    let offsets = manhattan_offsets(2);
+    for seed in 0..50 {
+        let mut space = CubeGrid::new(6, 6, 1, |_, _, _| all(3));
+        let mut solver = Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed)).unwrap();
+        let constraint =
+            ExclusionConstraint::new(&Cell::new_final(&0), &Cell::new_final(&1), &offsets);
+        solver.add_constraint(constraint).unwrap();
+        solver.run().unwrap();
+        drop(solver);
+
+        for (x, z) in (0..6).flat_map(|x| (0..6).map(move |z| (x, z))) {
+            if space[(x, 0, z)] != Cell::new_final(&0) {
+                continue;
+            }
+            for (dx, _, dz) in offsets.iter().filter(|(_, dy, _)| *dy == 0) {
+                let near = (x + dx, 0, z + dz);
+                if space.in_bounds(near) {
+                    assert_ne!(space[near], Cell::new_final(&1), "seed {}", seed);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn requirement_holds_for_every_cell() {
+    let rule = permissive_rule(16);
+    let offsets = manhattan_offsets(2);
+    let mut succeeded = 0;
+    for seed in 0..30 {
+        let mut space = CubeGrid::new(8, 8, 1, |_, _, _| all(16));
+        let mut solver = Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed)).unwrap();
+        let constraint =
+            RequirementConstraint::new(&Cell::new_final(&0), &Cell::new_final(&1), &offsets);
+        solver.add_constraint(constraint).unwrap();
+        if solver.run().is_err() {
+            continue;
+        }
+        drop(solver);
+        succeeded += 1;
+
+        for (x, z) in (0..8).flat_map(|x| (0..8).map(move |z| (x, z))) {
+            if space[(x, 0, z)] != Cell::new_final(&0) {
+                continue;
+            }
+            let satisfied = offsets.iter().any(|(dx, dy, dz)| {
+                let near = (x + dx, *dy, z + dz);
+                space.in_bounds(near) && space[near] == Cell::new_final(&1)
+            });
+            assert!(satisfied, "seed {}", seed);
+        }
+    }
+    assert!(succeeded >= 27, "only {} of 30 succeeded", succeeded);
+}
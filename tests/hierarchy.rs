@@ -0,0 +1,72 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use wfc3d::closure_rule::ClosureRule;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::hierarchy::{collapse_hierarchical, LayerContradiction};
+use wfc3d::set_rule::SetCollapseObserver;
+use wfc3d::Final;
+
+// Coarse cells in state 0 hold fine states 0 and 1, and cells in state 1 hold
+// fine states 2 and 3
+fn allowed(coarse: u8) -> Cell {
+    Cell::new(&[coarse * 2, coarse * 2 + 1])
+}
+
+#[test]
+fn fine_layer_stays_within_coarse_layout() {
+    for seed in 0..10 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut coarse = CubeGrid::new(3, 3, 1, |_, _, _| all(2));
+        let fine = collapse_hierarchical(
+            &mut coarse,
+            &permissive_rule(2),
+            &step_rule(4),
+            2,
+            |cell, _| allowed(cell.state.get().unwrap()),
+            &mut rng,
+        )
+        .unwrap();
+
+        for coord in wfc3d::Space::coordinate_list(&fine).iter() {
+            let (x, y, z) = *coord;
+            let value: u8 = fine[*coord].get().unwrap();
+            let coarse_value: u8 = coarse[(x / 2, y / 2, z / 2)].get().unwrap();
+            assert!(
+                allowed(coarse_value).hashset.contains(&value),
+                "seed {}",
+                seed
+            );
+        }
+    }
+}
+
+// Ignores the states left in a cell
+struct AlwaysZero;
+
+impl<C> SetCollapseObserver<Cell, C> for AlwaysZero {
+    fn observe(&self, _: C, cell: &mut Cell, _: &[Option<Cell>], _: &mut dyn RngCore) {
+        *cell = Cell::new_final(&0);
+    }
+}
+
+#[test]
+fn fine_cells_outside_the_layout_fail() {
+    let fine_rule = ClosureRule::new(
+        &AXES,
+        |_: &(isize, isize, isize), _: &mut Cell, _: &[Option<Cell>]| {},
+        AlwaysZero,
+    );
+    let mut coarse = CubeGrid::new(2, 1, 1, |_, _, _| Cell::new_final(&1));
+    let result = collapse_hierarchical(
+        &mut coarse,
+        &permissive_rule(2),
+        &fine_rule,
+        2,
+        |cell, _| allowed(cell.state.get().unwrap()),
+        &mut StdRng::seed_from_u64(0),
+    );
+    assert!(matches!(result, Err(LayerContradiction::Fine(_))));
+}
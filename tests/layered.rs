@@ -0,0 +1,90 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::layered::{LayeredRule, PairState};
+use wfc3d::{Budget, Final, SetState, Solver};
+
+type Pair = PairState<Cell, Cell>;
+type InnerRule = LayeredRule<Cell, Cell, StepRule, StepRule, CubeGrid<Cell>, CubeGrid<Cell>>;
+type OuterRule = LayeredRule<Pair, Cell, InnerRule, StepRule, CubeGrid<Pair>, CubeGrid<Cell>>;
+
+fn cells(states: &[u8]) -> Cell {
+    Cell::new(states)
+}
+
+#[test]
+fn pair_sets_are_products_of_layers() {
+    let pair = Pair::new(cells(&[0, 1]), cells(&[0, 1, 2]));
+    assert!(pair.has_any_of(&Pair::new(cells(&[1]), cells(&[2]))));
+    assert!(!pair.has_any_of(&Pair::new(cells(&[2]), cells(&[0]))));
+
+    let mut cleared = pair.clone();
+    cleared.clear_states(&Pair::new(cells(&[0, 1, 2]), cells(&[2])));
+    assert_eq!(cleared, Pair::new(cells(&[0, 1]), cells(&[0, 1])));
+
+    let mut retained = pair.clone();
+    retained.retain_states(&Pair::new(cells(&[1, 2]), cells(&[0])));
+    assert_eq!(retained, Pair::new(cells(&[1]), cells(&[0])));
+
+    let mut finals = Vec::new();
+    pair.collect_final_states(&mut finals);
+    assert_eq!(finals.len(), 6);
+    assert_eq!(Pair::new(cells(&[1]), cells(&[2])).get(), Some((1, 2)));
+    assert_eq!(pair.get(), None);
+}
+
+#[test]
+fn layered_rules_nest_for_more_layers() {
+    // The second layer is 0 wherever the first is, and the third layer is 2
+    // wherever the first is 0
+    let inner: InnerRule = LayeredRule::new(step_rule(3), permissive_rule(3))
+        .compatible(&cells(&[0]), &cells(&[0]))
+        .compatible(&cells(&[1, 2]), &all(3));
+    let outer: OuterRule = LayeredRule::new(inner, permissive_rule(3))
+        .compatible(&Pair::new(cells(&[0]), all(3)), &cells(&[2]))
+        .compatible(&Pair::new(cells(&[1, 2]), all(3)), &all(3));
+
+    for seed in 0..10 {
+        let open = PairState::new(Pair::new(all(3), all(3)), all(3));
+        let mut space = CubeGrid::new(5, 5, 2, |_, _, _| open.clone());
+        let mut solver = Solver::with_rng(&mut space, &outer, StdRng::seed_from_u64(seed)).unwrap();
+        let corner = PairState::new(Pair::new(cells(&[0]), all(3)), all(3));
+        solver.restrict((0, 0, 0), &corner).unwrap();
+        solver.run().unwrap();
+        drop(solver);
+
+        wfc3d::verify(&space, &outer).unwrap();
+        assert_eq!(space[(0, 0, 0)].get(), Some(((0, 0), 2)));
+        for y in 0..2 {
+            for z in 0..5 {
+                for x in 0..5 {
+                    let ((first, second), third) = space[(x, y, z)].get().unwrap();
+                    if first == 0 {
+                        assert_eq!((second, third), (0, 2));
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn incompatible_nested_layers_fail() {
+    // The inner layers only combine into (0, 0) and (1, 1), while the outer
+    // rule only accepts (0, 1) and (1, 0)
+    let inner: InnerRule = LayeredRule::new(permissive_rule(2), permissive_rule(2))
+        .compatible(&cells(&[0]), &cells(&[0]))
+        .compatible(&cells(&[1]), &cells(&[1]));
+    let outer: OuterRule = LayeredRule::new(inner, permissive_rule(2))
+        .compatible(&Pair::new(cells(&[0]), cells(&[1])), &all(2))
+        .compatible(&Pair::new(cells(&[1]), cells(&[0])), &all(2));
+
+    let open = PairState::new(Pair::new(all(2), all(2)), all(2));
+    let mut space = CubeGrid::new(2, 1, 1, |_, _, _| open.clone());
+    let mut solver = Solver::with_rng(&mut space, &outer, StdRng::seed_from_u64(0)).unwrap();
+    let budget = Budget::new().max_observations(10);
+    assert!(solver.run_with_budget(&budget).is_err());
+}
@@ -0,0 +1,95 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::set_rule::*;
+use wfc3d::Solver;
+
+type Coord = (isize, isize, isize);
+
+// A rule which allows any neighbors, choosing states with `observer`
+fn permissive_with<O: SetCollapseObserver<Cell, Coord> + Clone>(
+    observer: O,
+    states: u8,
+) -> SetCollapseRule<Cell, CubeGrid<Cell>, O> {
+    let mut builder = SetCollapseRuleBuilder::new(observer, all(states));
+    for s in 0..states {
+        let neighbors: Vec<_> = AXES.iter().map(|d| (*d, all(states))).collect();
+        builder = builder.allow(&Cell::new_final(&s), &neighbors);
+    }
+    builder.build()
+}
+
+#[test]
+fn zero_weight_positions_are_never_chosen() {
+    // No 0 on the bottom layer, and no 2 on the -x half
+    let observer = PositionalWeightedObserver::new(|(x, y, _): &Coord, state: &u8| match state {
+        0 if *y == 0 => 0,
+        2 if *x < 3 => 0,
+        _ => 1,
+    });
+    let rule = permissive_with(observer, 3);
+    for seed in 0..10 {
+        let mut space = CubeGrid::new(6, 6, 3, |_, _, _| all(3));
+        Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed))
+            .unwrap()
+            .run()
+            .unwrap();
+        for (x, y, z) in
+            (0..6).flat_map(|x| (0..3).flat_map(move |y| (0..6).map(move |z| (x, y, z))))
+        {
+            let cell = &space[(x, y, z)];
+            if y == 0 {
+                assert_ne!(*cell, Cell::new_final(&0), "seed {}", seed);
+            }
+            if x < 3 {
+                assert_ne!(*cell, Cell::new_final(&2), "seed {}", seed);
+            }
+        }
+    }
+}
+
+#[test]
+fn positions_with_only_zero_weights_still_resolve() {
+    let observer = PositionalWeightedObserver::new(|_: &Coord, _: &u8| 0);
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut cell = all(3);
+    observer.observe((0, 0, 0), &mut cell, &[], &mut rng);
+    assert_eq!(cell.hashset.len(), 1);
+}
+
+#[test]
+fn identical_neighbors_are_penalized() {
+    // A state next to one copy of itself is a quarter as likely
+    let observer = ContextualObserver::new(penalize_identical_neighbors(4));
+    let mut rng = StdRng::seed_from_u64(0);
+    let neighbors = [Some(Cell::new_final(&0)), None];
+    let mut zeros = 0;
+    for _ in 0..1000 {
+        let mut cell = all(2);
+        observer.observe((0, 0, 0), &mut cell, &neighbors, &mut rng);
+        if cell == Cell::new_final(&0) {
+            zeros += 1;
+        }
+    }
+    assert!((100..300).contains(&zeros), "{} zeros", zeros);
+}
+
+#[test]
+fn identical_neighbors_can_be_ruled_out() {
+    // With a third state always left, no cell ends up next to itself
+    let observer = ContextualObserver::new(penalize_identical_neighbors(u32::MAX));
+    let rule = permissive_with(observer, 3);
+    for seed in 0..10 {
+        let mut space = CubeGrid::new(30, 1, 1, |_, _, _| all(3));
+        Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed))
+            .unwrap()
+            .run()
+            .unwrap();
+        for x in 1..30 {
+            assert_ne!(space[(x - 1, 0, 0)], space[(x, 0, 0)], "seed {}", seed);
+        }
+    }
+}
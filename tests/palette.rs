@@ -0,0 +1,50 @@
+mod common;
+
+use common::*;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::palette::{Indices, PaletteGrid};
+
+fn layered_grid() -> CubeGrid<Cell> {
+    CubeGrid::new(5, 4, 3, |x, y, _| {
+        Cell::new_final(&(y as u8 + (x == 2) as u8))
+    })
+}
+
+#[test]
+fn run_length_round_trip() {
+    let grid = layered_grid();
+    let palette = PaletteGrid::from_grid(&grid).unwrap().run_length_encoded();
+    assert!(matches!(palette.indices, Indices::RunLength(_)));
+    assert_eq!(palette.indices.len(), Some(60));
+    let restored = palette.to_grid(Cell::new_final).unwrap();
+    assert_eq!(values(&restored), values(&grid));
+}
+
+#[test]
+fn oversized_runs_are_rejected_before_expanding() {
+    let mut palette = PaletteGrid::from_grid(&layered_grid()).unwrap();
+    palette.indices = Indices::RunLength(vec![(u32::MAX, 0); 4]);
+    assert!(palette.to_grid(Cell::new_final).is_none());
+
+    palette.indices = Indices::RunLength(vec![(u32::MAX, 0); 1 << 10]);
+    palette.width = isize::MAX;
+    assert!(palette.to_grid(Cell::new_final).is_none());
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn deserialized_grids_must_fill_their_size() {
+    let overflowing = format!(
+        r#"{{"cells":[],"width":{},"length":{},"height":2}}"#,
+        isize::MAX,
+        isize::MAX
+    );
+    assert!(serde_json::from_str::<CubeGrid<u8>>(&overflowing).is_err());
+    let short = r#"{"cells":[1],"width":2,"length":1,"height":1}"#;
+    assert!(serde_json::from_str::<CubeGrid<u8>>(short).is_err());
+    let exact = r#"{"cells":[1,2],"width":2,"length":1,"height":1}"#;
+    assert_eq!(
+        serde_json::from_str::<CubeGrid<u8>>(exact).unwrap()[(1, 0, 0)],
+        2
+    );
+}
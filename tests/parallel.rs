@@ -0,0 +1,53 @@
+#![cfg(feature = "parallel")]
+
+mod common;
+
+use common::*;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::parallel::collapse_parallel;
+
+fn collapse_on_threads(threads: usize, space: &CubeGrid<Cell>, rule: &StepRule) -> Vec<u8> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    let mut space = space.clone();
+    pool.install(|| collapse_parallel(&mut space, rule, (4, 4, 4), 7))
+        .unwrap();
+    wfc3d::verify(&space, rule).unwrap();
+    values(&space)
+}
+
+#[test]
+fn same_output_on_any_number_of_threads() {
+    let rule = step_rule(5);
+    let space = CubeGrid::new(17, 13, 6, |_, _, _| all(5));
+    let single = collapse_on_threads(1, &space, &rule);
+    for threads in [2, 3, 8] {
+        assert_eq!(collapse_on_threads(threads, &space, &rule), single);
+    }
+}
+
+#[test]
+fn wrapped_faces_are_consistent() {
+    let rule = step_rule(5);
+    for seed in 0..10 {
+        let mut space = CubeGrid::new(12, 10, 3, |_, _, _| all(5)).wrapping(true, false, true);
+        collapse_parallel(&mut space, &rule, (4, 4, 4), seed).unwrap();
+        assert_eq!(wfc3d::verify(&space, &rule).map_err(|v| v.len()), Ok(()));
+    }
+}
+
+#[test]
+fn blocks_keep_the_rules_of_collapsed_blocks() {
+    // Blocks collapsed before their neighbors can't always be kept, but a
+    // successful collapse has to satisfy the rule
+    let rule = asymmetric_rule();
+    for seed in 0..20 {
+        let mut space = CubeGrid::new(12, 1, 1, |_, _, _| all(2));
+        if collapse_parallel(&mut space, &rule, (3, 1, 1), seed).is_ok() {
+            let violations = wfc3d::verify(&space, &rule).map_err(|v| v.len());
+            assert_eq!(violations, Ok(()), "seed {}", seed);
+        }
+    }
+}
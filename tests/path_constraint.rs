@@ -0,0 +1,111 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::VecDeque;
+use std::ops::RangeBounds;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::path_constraint::PathConstraint;
+use wfc3d::{Contradiction, Solver};
+
+// The far corner of a square grid, where the path ends
+fn far_corner(size: isize) -> (isize, isize, isize) {
+    (size - 1, 0, size - 1)
+}
+
+// The length of the shortest path between the corners through cells in
+// state 1
+fn shortest_path(space: &CubeGrid<Cell>) -> Option<usize> {
+    let walkable =
+        |coord: (isize, isize, isize)| space.in_bounds(coord) && space[coord].hashset.contains(&1);
+    let mut distances = CubeGrid::new(space.width(), space.length(), space.height(), |_, _, _| {
+        None
+    });
+    let to = far_corner(space.width());
+    let mut to_visit = VecDeque::from([(0, 0, 0)]);
+    distances[(0, 0, 0)] = Some(0);
+    while let Some(coord) = to_visit.pop_front() {
+        let distance = distances[coord].unwrap();
+        if coord == to {
+            return Some(distance);
+        }
+        for (dx, dy, dz) in AXES {
+            let next = (coord.0 + dx, coord.1 + dy, coord.2 + dz);
+            if walkable(next) && distances[next].is_none() {
+                distances[next] = Some(distance + 1);
+                to_visit.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+// Collapses a square grid of `size` by `size` cells with a path between its
+// corners
+fn try_collapse_with_path(
+    seed: u64,
+    size: isize,
+    length: impl RangeBounds<usize>,
+) -> Result<CubeGrid<Cell>, Contradiction<(isize, isize, isize)>> {
+    let rule = permissive_rule(2);
+    let mut space = CubeGrid::new(size, size, 1, |_, _, _| all(2));
+    let mut solver = Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed))?;
+    let path = PathConstraint::new(&Cell::new_final(&1), &AXES, (0, 0, 0), far_corner(size))
+        .length(length);
+    solver.add_constraint(path)?;
+    solver.run()?;
+    drop(solver);
+    Ok(space)
+}
+
+fn collapse_with_path(seed: u64, size: isize, length: impl RangeBounds<usize>) -> CubeGrid<Cell> {
+    try_collapse_with_path(seed, size, length).unwrap()
+}
+
+#[test]
+fn ends_are_connected() {
+    for seed in 0..20 {
+        let space = collapse_with_path(seed, 6, ..);
+        assert!(shortest_path(&space).is_some(), "seed {}", seed);
+    }
+}
+
+#[test]
+fn path_is_no_longer_than_the_maximum() {
+    for seed in 0..20 {
+        let space = collapse_with_path(seed, 6, ..=10);
+        assert_eq!(shortest_path(&space), Some(10), "seed {}", seed);
+    }
+}
+
+#[test]
+fn maximum_length_on_a_large_grid() {
+    // The shortest possible path is 62 steps long
+    let space = collapse_with_path(0, 32, ..=70);
+    assert!(shortest_path(&space).unwrap() <= 70);
+}
+
+#[test]
+fn path_is_no_shorter_than_the_minimum() {
+    // The minimum is only kept one cell ahead, so some seeds run into a
+    // contradiction. The shortest possible path is 22 steps long.
+    let mut succeeded = 0;
+    for seed in 0..30 {
+        if let Ok(space) = try_collapse_with_path(seed, 12, 26..) {
+            assert!(shortest_path(&space).unwrap() >= 26, "seed {}", seed);
+            succeeded += 1;
+        }
+    }
+    assert!(succeeded >= 10, "only {} of 30 seeds succeeded", succeeded);
+}
+
+#[test]
+fn blocked_ends_fail() {
+    let rule = permissive_rule(2);
+    let mut space = CubeGrid::new(3, 1, 1, |x, _, _| Cell::new_final(&[1, 0, 1][x as usize]));
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    let path = PathConstraint::new(&Cell::new_final(&1), &AXES, (0, 0, 0), (2, 0, 0));
+    let result = solver.add_constraint(path).and_then(|_| solver.run());
+    assert!(result.is_err());
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::*;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::{Contradiction, Solver};
+
+#[test]
+fn pin_requires_a_final_state() {
+    let rule = step_rule(4);
+    let mut space = CubeGrid::new(3, 1, 1, |_, _, _| all(4));
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    assert_eq!(
+        solver.pin((1, 0, 0), &Cell::new(&[0, 3])),
+        Err(Contradiction {
+            coordinate: (1, 0, 0)
+        })
+    );
+    assert!(solver.pin((1, 0, 0), &Cell::new(&[])).is_err());
+    drop(solver);
+    // The cell is left as it was
+    assert_eq!(space[(1, 0, 0)], all(4));
+}
+
+#[test]
+fn pin_sets_a_final_state() {
+    let rule = step_rule(4);
+    let mut space = CubeGrid::new(3, 1, 1, |_, _, _| all(4));
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    solver.pin((0, 0, 0), &Cell::new_final(&3)).unwrap();
+    solver.run().unwrap();
+    drop(solver);
+    assert_eq!(values(&space)[0], 3);
+}
+
+#[test]
+fn failed_restrict_leaves_the_cell_usable() {
+    let rule = step_rule(4);
+    let mut space = CubeGrid::new(3, 1, 1, |_, _, _| all(4));
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    solver.pin((0, 0, 0), &Cell::new_final(&0)).unwrap();
+    assert_eq!(
+        solver.restrict((1, 0, 0), &Cell::new(&[2, 3])),
+        Err(Contradiction {
+            coordinate: (1, 0, 0)
+        })
+    );
+    // Another placement still works
+    solver.pin((1, 0, 0), &Cell::new_final(&1)).unwrap();
+    solver.run().unwrap();
+    drop(solver);
+    assert_eq!(space[(1, 0, 0)], Cell::new_final(&1));
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::*;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::{Cause, CollapseRule, Solver};
+
+// The values of each final state in `states`, sorted
+fn sorted(states: &[Cell]) -> Vec<u8> {
+    let mut values: Vec<u8> = states
+        .iter()
+        .flat_map(|state| state.hashset.iter().copied())
+        .collect();
+    values.sort();
+    values
+}
+
+#[test]
+fn explain_is_empty_without_recording() {
+    let rule = step_rule(4);
+    let mut space = CubeGrid::new(3, 1, 1, |_, _, _| all(4));
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    solver.pin((0, 0, 0), &Cell::new_final(&0)).unwrap();
+    assert!(solver.explain((2, 0, 0)).is_empty());
+}
+
+#[test]
+fn explain_traces_narrowings_back_to_the_start() {
+    let rule = step_rule(4);
+    let mut space = CubeGrid::new(3, 1, 1, |_, _, _| all(4));
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    solver.record_provenance();
+    solver.pin((0, 0, 0), &Cell::new_final(&0)).unwrap();
+
+    let trace = solver.explain((2, 0, 0));
+    let steps: Vec<_> = trace
+        .iter()
+        .map(|step| (step.coordinate, step.state.clone()))
+        .collect();
+    assert_eq!(
+        steps,
+        [
+            ((0, 0, 0), Cell::new_final(&0)),
+            ((1, 0, 0), Cell::new(&[0, 1])),
+            ((2, 0, 0), Cell::new(&[0, 1, 2])),
+        ]
+    );
+    assert_eq!(trace[0].causes, [Cause::Restricted]);
+}
+
+#[test]
+fn neighbor_causes_name_the_rule_entries() {
+    let rule = step_rule(4);
+    let offsets = rule.neighbor_offsets();
+    let mut space = CubeGrid::new(3, 1, 1, |_, _, _| all(4));
+    let mut solver = Solver::new(&mut space, &rule).unwrap();
+    solver.record_provenance();
+    solver.pin((0, 0, 0), &Cell::new_final(&3)).unwrap();
+
+    let trace = solver.explain((1, 0, 0));
+    let step = trace.last().unwrap();
+    assert_eq!(step.coordinate, (1, 0, 0));
+    let [Cause::Neighbor {
+        neighbor,
+        offset_index,
+        neighbor_state,
+        removed,
+    }] = &step.causes[..]
+    else {
+        panic!("unexpected causes {:?}", step.causes);
+    };
+    assert_eq!(*neighbor, (0, 0, 0));
+    assert_eq!(offsets[*offset_index], (-1, 0, 0));
+    assert_eq!(*neighbor_state, Cell::new_final(&3));
+    // Neither 0 nor 1 allow a 3 on their -x side
+    assert_eq!(sorted(removed), [0, 1]);
+}
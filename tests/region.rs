@@ -0,0 +1,65 @@
+mod common;
+
+use common::*;
+use wfc3d::cube_grid::{CubeBox, CubeGrid};
+use wfc3d::set_rule::*;
+use wfc3d::{regenerate_region, verify, Final};
+
+#[test]
+fn regenerating_leaves_open_cells_outside_untouched() {
+    // State 2 is only allowed at x < 2, which the rule applies when cells
+    // are initialized
+    let mut builder = SetCollapseRuleBuilder::new(UniformSetCollapseObserver, all(3));
+    for s in 0..3 {
+        let neighbors: Vec<_> = AXES.iter().map(|d| (*d, all(3))).collect();
+        builder = builder.allow(&Cell::new_final(&s), &neighbors);
+    }
+    let rule = builder
+        .restrict_to_region(&Cell::new_final(&2), |c: &(isize, isize, isize)| c.0 < 2)
+        .build();
+
+    let mut space = CubeGrid::new(6, 1, 1, |_, _, _| all(3));
+    let region = CubeBox {
+        min: (4, 0, 0),
+        max: (6, 1, 1),
+    };
+    regenerate_region(&mut space, &rule, &region, &all(3)).unwrap();
+    for x in 0..4 {
+        assert_eq!(space[(x, 0, 0)], all(3));
+    }
+    for x in 4..6 {
+        let value: Option<u8> = space[(x, 0, 0)].get();
+        assert!(matches!(value, Some(0 | 1)));
+    }
+}
+
+#[test]
+fn failed_regeneration_restores_the_region() {
+    let rule = step_rule(5);
+    let mut space = CubeGrid::new(3, 1, 1, |x, _, _| Cell::new_final(&[0, 2, 4][x as usize]));
+    let before = space.clone();
+    let region = CubeBox {
+        min: (1, 0, 0),
+        max: (2, 1, 1),
+    };
+    // No state is within one of both 0 and 4
+    assert!(regenerate_region(&mut space, &rule, &region, &all(5)).is_err());
+    for x in 0..3 {
+        assert_eq!(space[(x, 0, 0)], before[(x, 0, 0)]);
+    }
+}
+
+#[test]
+fn regenerated_cells_keep_the_rules_of_fixed_cells() {
+    let rule = asymmetric_rule();
+    let region = CubeBox {
+        min: (1, 0, 0),
+        max: (2, 1, 1),
+    };
+    for _ in 0..50 {
+        let mut space = CubeGrid::new(2, 1, 1, |_, _, _| Cell::new_final(&0));
+        // Either the region is regenerated as 0, or it is left as it was
+        let _ = regenerate_region(&mut space, &rule, &region, &all(2));
+        assert_eq!(verify(&space, &rule), Ok(()));
+    }
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wfc3d::collapse_with_retries;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::Solver;
+
+#[test]
+fn first_success_is_returned_with_its_stats() {
+    let rule = permissive_rule(3);
+    let mut rng = StdRng::seed_from_u64(0);
+    let factory = || CubeGrid::new(4, 4, 1, |_, _, _| all(3));
+    let (space, stats) = collapse_with_retries(factory, &rule, 5, &mut rng).unwrap();
+    assert_eq!(values(&space).len(), 16);
+    assert_eq!(stats.attempts, 1);
+    assert_eq!(stats.seeds.len(), 1);
+    assert!(stats.contradictions.is_empty());
+    assert_eq!(stats.observations.len(), 1);
+    assert!(stats.observations[0] > 0);
+}
+
+#[test]
+fn every_attempt_is_counted_when_all_fail() {
+    // Nothing fits between a 0 and a 3
+    let rule = step_rule(4);
+    let factory = || {
+        CubeGrid::new(3, 1, 1, |x, _, _| match x {
+            0 => Cell::new_final(&0),
+            1 => all(4),
+            _ => Cell::new_final(&3),
+        })
+    };
+    let stats =
+        collapse_with_retries(factory, &rule, 3, &mut StdRng::seed_from_u64(0)).unwrap_err();
+    assert_eq!(stats.attempts, 3);
+    assert_eq!(stats.seeds.len(), 3);
+    assert_eq!(stats.contradictions.len(), 3);
+    assert_eq!(stats.observations, [0, 0, 0]);
+}
+
+#[test]
+fn attempts_can_be_repeated_from_their_seeds() {
+    let rule = asymmetric_rule();
+    let factory = || CubeGrid::new(6, 1, 1, |_, _, _| all(2));
+    // Find a collapse which needed a few attempts
+    let (space, stats) = (0..100)
+        .filter_map(|seed| {
+            collapse_with_retries(factory, &rule, 10, &mut StdRng::seed_from_u64(seed)).ok()
+        })
+        .find(|(_, stats)| stats.attempts > 1)
+        .unwrap();
+    assert_eq!(stats.contradictions.len(), stats.attempts - 1);
+
+    for (i, seed) in stats.seeds.iter().enumerate() {
+        let mut repeated = factory();
+        let mut solver =
+            Solver::with_rng(&mut repeated, &rule, StdRng::seed_from_u64(*seed)).unwrap();
+        let result = solver.run();
+        assert_eq!(solver.observations(), stats.observations[i]);
+        drop(solver);
+        match stats.contradictions.get(i) {
+            Some(contradiction) => assert_eq!(result, Err(*contradiction)),
+            None => {
+                assert_eq!(result, Ok(()));
+                assert_eq!(values(&repeated), values(&space));
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::*;
+use rand::SeedableRng;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::{Budget, ChaCha8Rng, Solver, SolverState, Status};
+
+#[test]
+fn resumed_run_matches_uninterrupted_run() {
+    let rule = step_rule(5);
+    let new_space = || CubeGrid::new(8, 8, 3, |_, _, _| all(5));
+
+    let mut uninterrupted = new_space();
+    Solver::with_rng(&mut uninterrupted, &rule, ChaCha8Rng::seed_from_u64(3))
+        .unwrap()
+        .run()
+        .unwrap();
+
+    let mut space = new_space();
+    let mut solver = Solver::with_rng(&mut space, &rule, ChaCha8Rng::seed_from_u64(3)).unwrap();
+    let status = solver
+        .run_with_budget(&Budget::new().max_observations(20))
+        .unwrap();
+    assert_eq!(status, Status::Interrupted);
+    let json = serde_json::to_string(&solver.save()).unwrap();
+    drop(solver);
+
+    let state: SolverState<CubeGrid<Cell>, (isize, isize, isize), ChaCha8Rng> =
+        serde_json::from_str(&json).unwrap();
+    let mut resumed = new_space();
+    Solver::resume(&mut resumed, &rule, state).run().unwrap();
+    assert_eq!(values(&resumed), values(&uninterrupted));
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::symmetry::{Symmetry, SymmetryConstraint, UnsupportedSymmetry};
+use wfc3d::Solver;
+
+// Swaps 1 and 2, like a tile and its mirrored variant, while 0 maps to itself
+fn swap(state: &Cell) -> Cell {
+    let values: Vec<u8> = state
+        .hashset
+        .iter()
+        .map(|value| match value {
+            1 => 2,
+            2 => 1,
+            other => *other,
+        })
+        .collect();
+    Cell::new(&values)
+}
+
+// The cell which `(x, y, z)` maps to in a grid of the given size
+fn image(
+    symmetry: Symmetry,
+    (width, height, length): (isize, isize, isize),
+    (x, y, z): (isize, isize, isize),
+) -> (isize, isize, isize) {
+    match symmetry {
+        Symmetry::MirrorX => (width - 1 - x, y, z),
+        Symmetry::MirrorY => (x, height - 1 - y, z),
+        Symmetry::MirrorZ => (x, y, length - 1 - z),
+        Symmetry::Rotate180 => (width - 1 - x, y, length - 1 - z),
+        Symmetry::Rotate90 => (length - 1 - z, y, x),
+    }
+}
+
+fn collapse_symmetric(
+    symmetry: Symmetry,
+    size: (isize, isize, isize),
+    seed: u64,
+) -> CubeGrid<Cell> {
+    let rule = permissive_rule(3);
+    let (width, height, length) = size;
+    let mut space = CubeGrid::new(width, length, height, |_, _, _| all(3));
+    let constraint = SymmetryConstraint::new(symmetry, &space, swap).unwrap();
+    let mut solver = Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed)).unwrap();
+    solver.add_constraint(constraint).unwrap();
+    solver.run().unwrap();
+    drop(solver);
+    space
+}
+
+#[test]
+fn every_symmetry_maps_cells_to_their_variants() {
+    for symmetry in [
+        Symmetry::MirrorX,
+        Symmetry::MirrorY,
+        Symmetry::MirrorZ,
+        Symmetry::Rotate180,
+        Symmetry::Rotate90,
+    ] {
+        let size = (4, 3, 4);
+        for seed in 0..5 {
+            let space = collapse_symmetric(symmetry, size, seed);
+            for x in 0..size.0 {
+                for y in 0..size.1 {
+                    for z in 0..size.2 {
+                        let mapped = image(symmetry, size, (x, y, z));
+                        assert_eq!(
+                            space[mapped],
+                            swap(&space[(x, y, z)]),
+                            "{:?} at {:?}, seed {}",
+                            symmetry,
+                            (x, y, z),
+                            seed
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn cells_on_the_axis_keep_states_which_map_to_themselves() {
+    for seed in 0..5 {
+        let space = collapse_symmetric(Symmetry::MirrorX, (3, 1, 3), seed);
+        for z in 0..3 {
+            assert_eq!(space[(1, 0, z)], Cell::new_final(&0), "seed {}", seed);
+        }
+    }
+}
+
+#[test]
+fn quarter_turns_need_a_square_grid() {
+    let space = CubeGrid::new(4, 3, 1, |_, _, _| all(3));
+    assert_eq!(
+        SymmetryConstraint::new(Symmetry::Rotate90, &space, swap).err(),
+        Some(UnsupportedSymmetry {
+            symmetry: Symmetry::Rotate90,
+            size: (4, 1, 3),
+        })
+    );
+    assert!(SymmetryConstraint::new(Symmetry::Rotate180, &space, swap).is_ok());
+}
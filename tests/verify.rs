@@ -0,0 +1,86 @@
+mod common;
+
+use common::*;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::set_rule::{SetCollapseRuleBuilder, UniformSetCollapseObserver};
+use wfc3d::{verify, Solver, Violation};
+
+#[test]
+fn collapsed_space_passes() {
+    let rule = step_rule(4);
+    for seed in 0..10 {
+        let mut space = CubeGrid::new(5, 5, 5, |_, _, _| all(4));
+        Solver::with_rng(&mut space, &rule, StdRng::seed_from_u64(seed))
+            .and_then(|mut solver| solver.run())
+            .unwrap();
+        assert_eq!(verify(&space, &rule), Ok(()), "seed {}", seed);
+    }
+}
+
+#[test]
+fn incompatible_neighbors_are_found() {
+    let rule = step_rule(4);
+    let mut space = CubeGrid::new(3, 1, 1, |x, _, _| Cell::new_final(&[0, 1, 3][x as usize]));
+    let violations = verify(&space, &rule).unwrap_err();
+    assert_eq!(
+        violations,
+        [
+            Violation::Incompatible {
+                coordinate: (1, 0, 0),
+                state: Cell::new_final(&1),
+                neighbor: (2, 0, 0),
+                neighbor_state: Cell::new_final(&3),
+            },
+            Violation::Incompatible {
+                coordinate: (2, 0, 0),
+                state: Cell::new_final(&3),
+                neighbor: (1, 0, 0),
+                neighbor_state: Cell::new_final(&1),
+            },
+        ]
+    );
+
+    space[(2, 0, 0)] = Cell::new_final(&2);
+    assert_eq!(verify(&space, &rule), Ok(()));
+}
+
+#[test]
+fn unresolved_cells_are_found() {
+    let rule = step_rule(4);
+    let space = CubeGrid::new(2, 1, 1, |x, _, _| {
+        if x == 0 {
+            Cell::new_final(&0)
+        } else {
+            Cell::new(&[0, 1])
+        }
+    });
+    assert_eq!(
+        verify(&space, &rule),
+        Err(vec![Violation::Unresolved {
+            coordinate: (1, 0, 0),
+            state: Cell::new(&[0, 1]),
+        }])
+    );
+}
+
+#[test]
+fn disallowed_states_are_found() {
+    let mut builder = SetCollapseRuleBuilder::new(UniformSetCollapseObserver, all(2));
+    for s in 0..2 {
+        let neighbors: Vec<_> = AXES.iter().map(|d| (*d, all(2))).collect();
+        builder = builder.allow(&Cell::new_final(&s), &neighbors);
+    }
+    let rule = builder
+        .restrict_to_region(&Cell::new_final(&1), |c: &(isize, isize, isize)| c.0 < 1)
+        .build();
+    let space = CubeGrid::new(2, 1, 1, |_, _, _| Cell::new_final(&1));
+    assert_eq!(
+        verify(&space, &rule),
+        Err(vec![Violation::Disallowed {
+            coordinate: (1, 0, 0),
+            state: Cell::new_final(&1),
+        }])
+    );
+}
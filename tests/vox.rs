@@ -0,0 +1,171 @@
+use wfc3d::cube_grid::CubeGrid;
+use wfc3d::vox::{read_vox, write_vox, VoxPalette, MAX_MODEL_SIZE};
+
+fn round_trip(grid: &CubeGrid<u8>, palette: Option<&VoxPalette>) -> wfc3d::vox::Vox {
+    let mut file = Vec::new();
+    write_vox(grid, |index| *index, palette, &mut file).unwrap();
+    read_vox(&file[..]).unwrap()
+}
+
+fn assert_same(a: &CubeGrid<u8>, b: &CubeGrid<u8>) {
+    assert_eq!(
+        (a.width(), a.length(), a.height()),
+        (b.width(), b.length(), b.height())
+    );
+    for y in 0..a.height() {
+        for z in 0..a.length() {
+            for x in 0..a.width() {
+                assert_eq!(a[(x, y, z)], b[(x, y, z)], "at {:?}", (x, y, z));
+            }
+        }
+    }
+}
+
+#[test]
+fn single_model_round_trip() {
+    let grid = CubeGrid::new(7, 3, 5, |x, y, z| ((x * 3 + y * 5 + z) % 4) as u8);
+    let vox = round_trip(&grid, None);
+    assert_same(&vox.grid, &grid);
+    assert!(vox.palette.is_none());
+}
+
+#[test]
+fn large_grid_round_trip() {
+    // Wider and taller than a single model, so it is split into several
+    let (width, length, height) = (300, 5, 270);
+    assert!(width > MAX_MODEL_SIZE && height > MAX_MODEL_SIZE);
+    // Corners are filled so that the grid read back starts at the same place
+    let grid = CubeGrid::new(width, length, height, |x, y, z| {
+        let corner = (x == 0 || x == width - 1) && (y == 0 || y == height - 1);
+        if corner {
+            1
+        } else {
+            ((x + 2 * y + 3 * z) % 5) as u8
+        }
+    });
+    let mut palette = [[0; 4]; 256];
+    for (i, color) in palette.iter_mut().enumerate() {
+        *color = [i as u8, 255 - i as u8, (i * 7) as u8, 255];
+    }
+
+    let vox = round_trip(&grid, Some(&palette));
+    assert_same(&vox.grid, &grid);
+    assert_eq!(vox.palette.unwrap()[1..], palette[1..]);
+}
+
+#[test]
+fn empty_grid_is_rejected() {
+    let grid = CubeGrid::new(0, 4, 4, |_, _, _| 1u8);
+    assert!(write_vox(&grid, |index| *index, None, Vec::new()).is_err());
+}
+
+#[test]
+fn truncated_file_is_rejected() {
+    let grid = CubeGrid::new(4, 4, 4, |_, _, _| 1u8);
+    let mut file = Vec::new();
+    write_vox(&grid, |index| *index, None, &mut file).unwrap();
+    file.truncate(file.len() - 10);
+    assert!(read_vox(&file[..]).is_err());
+}
+
+fn chunk(id: &[u8; 4], content: &[u8]) -> Vec<u8> {
+    let mut chunk = id.to_vec();
+    chunk.extend((content.len() as u32).to_le_bytes());
+    chunk.extend(0u32.to_le_bytes());
+    chunk.extend(content);
+    chunk
+}
+
+fn file(chunks: &[Vec<u8>]) -> Vec<u8> {
+    let content = chunks.concat();
+    let mut file = b"VOX ".to_vec();
+    file.extend(150u32.to_le_bytes());
+    file.extend(b"MAIN");
+    file.extend(0u32.to_le_bytes());
+    file.extend((content.len() as u32).to_le_bytes());
+    file.extend(content);
+    file
+}
+
+fn numbers(values: &[i32]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn size(x: i32, y: i32, z: i32) -> Vec<u8> {
+    chunk(b"SIZE", &numbers(&[x, y, z]))
+}
+
+fn no_voxels() -> Vec<u8> {
+    chunk(b"XYZI", &numbers(&[0]))
+}
+
+fn transform(id: i32, child: i32, translation: &str) -> Vec<u8> {
+    let mut content = numbers(&[id, 0, child, -1, 0, 1, 1]);
+    content.extend(numbers(&[2]));
+    content.extend(b"_t");
+    content.extend(numbers(&[translation.len() as i32]));
+    content.extend(translation.as_bytes());
+    chunk(b"nTRN", &content)
+}
+
+fn shape(id: i32, model: i32) -> Vec<u8> {
+    chunk(b"nSHP", &numbers(&[id, 0, 1, model, 0]))
+}
+
+#[test]
+fn huge_models_are_rejected() {
+    let huge = file(&[size(100_000, 100_000, 100_000), no_voxels()]);
+    assert!(read_vox(&huge[..]).is_err());
+    let empty = file(&[size(0, 4, 4), no_voxels()]);
+    assert!(read_vox(&empty[..]).is_err());
+}
+
+#[test]
+fn models_placed_out_of_range_are_rejected() {
+    let far = format!("{} 0 0", isize::MAX);
+    let overflowing = file(&[
+        size(2, 2, 2),
+        no_voxels(),
+        transform(0, 1, &far),
+        shape(1, 0),
+    ]);
+    assert!(read_vox(&overflowing[..]).is_err());
+
+    let group = chunk(b"nGRP", &numbers(&[1, 0, 2, 2, 4]));
+    let apart = file(&[
+        size(2, 2, 2),
+        no_voxels(),
+        size(2, 2, 2),
+        no_voxels(),
+        transform(0, 1, "0 0 0"),
+        group,
+        transform(2, 3, "0 0 0"),
+        shape(3, 0),
+        transform(4, 5, "0 0 1000000000"),
+        shape(5, 1),
+    ]);
+    assert!(read_vox(&apart[..]).is_err());
+
+    // The same scene placed close together is read
+    let group = chunk(b"nGRP", &numbers(&[1, 0, 2, 2, 4]));
+    let close = file(&[
+        size(2, 2, 2),
+        no_voxels(),
+        size(2, 2, 2),
+        no_voxels(),
+        transform(0, 1, "0 0 0"),
+        group,
+        transform(2, 3, "0 0 0"),
+        shape(3, 0),
+        transform(4, 5, "0 0 10"),
+        shape(5, 1),
+    ]);
+    let vox = read_vox(&close[..]).unwrap();
+    assert_eq!(
+        (vox.grid.width(), vox.grid.length(), vox.grid.height()),
+        (2, 2, 12)
+    );
+}